@@ -0,0 +1,227 @@
+use crate::game::GameEvent;
+use crate::map::CoordDiff2;
+use crate::AnyError;
+use glam::Vec2;
+
+/// How an animation progresses from start to end.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Easing {
+    Linear,
+    /// Starts fast and slows down at the end.
+    EaseOut,
+    /// Starts and ends slowly.
+    EaseInOut,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 3] = [Easing::Linear, Easing::EaseOut, Easing::EaseInOut];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::EaseOut => "ease-out",
+            Easing::EaseInOut => "ease-in-out",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, AnyError> {
+        Easing::ALL
+            .into_iter()
+            .find(|easing| easing.name() == name)
+            .ok_or_else(|| format!("unknown easing '{}'", name).into())
+    }
+
+    /// Maps the elapsed fraction `t` of the animation, from 0 to 1, to its progress.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut if t < 0.5 => 2.0 * t * t,
+            Easing::EaseInOut => 1.0 - 2.0 * (1.0 - t) * (1.0 - t),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AnimationConfig {
+    /// Seconds that a step takes on the screen. 0 disables the animations.
+    pub duration: f32,
+    pub easing: Easing,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self {
+            duration: 0.1,
+            easing: Easing::EaseOut,
+        }
+    }
+}
+
+/// A monster sliding from `from` to the tile `to`, in world coordinates.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Motion {
+    from: Vec2,
+    to: CoordDiff2,
+    elapsed: f32,
+}
+
+/// Where things are drawn while the game state has already moved on. The game never waits for
+/// the animations: a move pressed during one is applied right away, and the animation continues
+/// from wherever it was drawn towards the new position.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Animator {
+    config: AnimationConfig,
+    /// Offset of the world in tiles when the last scroll started.
+    scroll_from: Vec2,
+    scroll_elapsed: f32,
+    motions: Vec<Motion>,
+}
+
+impl Animator {
+    pub fn new(config: AnimationConfig) -> Self {
+        Self {
+            config,
+            scroll_from: Vec2::ZERO,
+            scroll_elapsed: 0.0,
+            motions: Vec::new(),
+        }
+    }
+
+    /// Starts the animations for the events of a step.
+    pub fn handle(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::Moved(dir) => {
+                self.scroll_from = self.scroll() + dir.as_vec2();
+                self.scroll_elapsed = 0.0;
+            }
+            // monsters that wrap around the edges of the map just appear on the other side
+            GameEvent::MonsterMoved { from, to } if (to - from).abs().max_element() <= 1 => {
+                let from = self.monster_position(from);
+                self.motions.retain(|motion| motion.to != to);
+                self.motions.push(Motion {
+                    from,
+                    to,
+                    elapsed: 0.0,
+                });
+            }
+            GameEvent::Restarted | GameEvent::LevelStarted { .. } => self.reset(),
+            _ => {}
+        }
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        let duration = self.config.duration;
+        self.scroll_elapsed += delta;
+        for motion in &mut self.motions {
+            motion.elapsed += delta;
+        }
+        self.motions.retain(|motion| motion.elapsed < duration);
+    }
+
+    /// Stops every animation, e.g. after loading a game.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config);
+    }
+
+    /// Offset in tiles to draw the world with, so that it slides towards where the player is.
+    pub fn scroll(&self) -> Vec2 {
+        self.scroll_from * (1.0 - self.progress(self.scroll_elapsed))
+    }
+
+    /// Where to draw the monster that is on the tile `pos`, in world coordinates.
+    pub fn monster_position(&self, pos: CoordDiff2) -> Vec2 {
+        let to = pos.as_vec2();
+        match self.motions.iter().find(|motion| motion.to == pos) {
+            Some(motion) => motion.from.lerp(to, self.progress(motion.elapsed)),
+            None => to,
+        }
+    }
+
+    fn progress(&self, elapsed: f32) -> f32 {
+        if self.config.duration <= 0.0 {
+            1.0
+        } else {
+            self.config.easing.apply(elapsed / self.config.duration)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Action, GameState};
+    use crate::map::{Coord2, Tile, WorldMode, RIGHT};
+    use crate::monster::MonsterKind;
+
+    const LINEAR: AnimationConfig = AnimationConfig {
+        duration: 0.5,
+        easing: Easing::Linear,
+    };
+
+    #[test]
+    fn test_easings() {
+        for easing in Easing::ALL {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+            assert_eq!(Easing::from_name(easing.name()).unwrap(), easing);
+        }
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.25), 0.125);
+        assert!(Easing::from_name("bouncy").is_err());
+    }
+
+    #[test]
+    fn test_rendering_lags_behind_the_logic() {
+        let mut game_state = GameState::new(
+            Coord2::new(9, 7),
+            Coord2::new(4, 3),
+            42000,
+            WorldMode::Dream,
+        );
+        game_state
+            .map
+            .set(Coord2::new(7, 3), Tile::Monster(MonsterKind::Stalker));
+        let mut animator = Animator::new(LINEAR);
+        for _ in 1..game_state.config().monster_period_ticks {
+            game_state.step(Action::Idle);
+        }
+        for event in game_state.step(Action::MoveRight) {
+            animator.handle(&event);
+        }
+        // the player moved and the stalker stepped towards them on the same tick
+        assert_eq!(game_state.accumulated_pos, RIGHT);
+        let stalker = CoordDiff2::new(2, 0);
+        assert!(game_state.map.monster_at(Coord2::new(5, 3)).is_some());
+        assert_eq!(animator.scroll(), Vec2::new(1.0, 0.0));
+        assert_eq!(animator.monster_position(stalker), Vec2::new(3.0, 0.0));
+
+        animator.update(0.25);
+        assert_eq!(animator.scroll(), Vec2::new(0.5, 0.0));
+        assert_eq!(animator.monster_position(stalker), Vec2::new(2.5, 0.0));
+        animator.update(0.25);
+        assert_eq!(animator.scroll(), Vec2::ZERO);
+        assert_eq!(animator.monster_position(stalker), Vec2::new(2.0, 0.0));
+    }
+
+    #[test]
+    fn test_moves_during_an_animation() {
+        let mut animator = Animator::new(LINEAR);
+        animator.handle(&GameEvent::Moved(RIGHT));
+        animator.update(0.25);
+        animator.handle(&GameEvent::Moved(RIGHT));
+        assert_eq!(animator.scroll(), Vec2::new(1.5, 0.0));
+        animator.update(0.25);
+        assert_eq!(animator.scroll(), Vec2::new(0.75, 0.0));
+
+        let (a, b, c) = (CoordDiff2::ZERO, RIGHT, RIGHT * 2);
+        animator.handle(&GameEvent::MonsterMoved { from: a, to: b });
+        animator.update(0.25);
+        animator.handle(&GameEvent::MonsterMoved { from: b, to: c });
+        assert_eq!(animator.monster_position(c), Vec2::new(0.5, 0.0));
+        animator.handle(&GameEvent::MonsterMoved { from: c, to: -c });
+        assert_eq!(animator.monster_position(-c), -c.as_vec2());
+    }
+}
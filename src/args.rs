@@ -0,0 +1,157 @@
+use crate::animation::{AnimationConfig, Easing};
+use crate::camera::DEFAULT_MARGIN;
+use crate::clock::DEFAULT_TICK;
+use crate::game::Pacing;
+use crate::map::{Coord, WorldMode};
+use crate::AnyError;
+
+pub const USAGE: &str = "usage: dream-maze [--seed <number>] [--lucid] [--world <file>]
+                  [--tick <seconds>] [--turn-based] [--margin <tiles>]
+                  [--animation <seconds>] [--easing <linear|ease-out|ease-in-out>]
+                  [--tileset <file>] [--theme <file>]
+                  [--record <file>] [--replay <file> [--headless]]
+  --seed <number>  seed of the generated world
+  --lucid          remember the world instead of forgetting whatever leaves the screen
+  --world <file>   lucid mode, loading the world from the file if it exists, and saving it there
+                   when quitting with Escape. Not with --replay, which has its own world
+  --tick <seconds> duration of a simulation step, 1/60 by default. Longer is slower
+  --turn-based     monsters only move after you do
+  --margin <tiles> how far beyond the edges of the window the world is simulated, 4 by default
+  --animation <seconds>
+                   how long a step takes on the screen, 0.1 by default. 0 disables animations
+  --easing <name>  how the animations speed up and slow down, ease-out by default
+  --tileset <file> draw with the sprites of this tileset mapping instead of dream_maze_tileset.json
+  --theme <file>   start with the colours of this theme instead of dream_maze_theme.json
+  --record <file>  save the session as a replay when quitting with Escape
+  --replay <file>  play back a recorded session, then continue playing from where it ended
+  --headless       with --replay, simulate the session without a window and print the outcome";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Args {
+    pub seed: u64,
+    pub mode: WorldMode,
+    pub world: Option<String>,
+    pub tick: f32,
+    pub pacing: Pacing,
+    pub margin: Coord,
+    pub animation: AnimationConfig,
+    pub tileset: Option<String>,
+    pub theme: Option<String>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub headless: bool,
+}
+
+impl Args {
+    pub fn parse(
+        mut args: impl Iterator<Item = String>,
+        default_seed: u64,
+    ) -> Result<Self, AnyError> {
+        let mut parsed = Self {
+            seed: default_seed,
+            mode: WorldMode::Dream,
+            world: None,
+            tick: DEFAULT_TICK,
+            pacing: Pacing::RealTime,
+            margin: DEFAULT_MARGIN,
+            animation: AnimationConfig::default(),
+            tileset: None,
+            theme: None,
+            record: None,
+            replay: None,
+            headless: false,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => parsed.seed = value_of(&arg, args.next())?.parse()?,
+                "--record" => parsed.record = Some(value_of(&arg, args.next())?),
+                "--replay" => parsed.replay = Some(value_of(&arg, args.next())?),
+                "--lucid" => parsed.mode = WorldMode::Lucid,
+                "--world" => {
+                    parsed.world = Some(value_of(&arg, args.next())?);
+                    parsed.mode = WorldMode::Lucid;
+                }
+                "--tick" => parsed.tick = value_of(&arg, args.next())?.parse()?,
+                "--turn-based" => parsed.pacing = Pacing::TurnBased,
+                "--tileset" => parsed.tileset = Some(value_of(&arg, args.next())?),
+                "--theme" => parsed.theme = Some(value_of(&arg, args.next())?),
+                "--margin" => parsed.margin = value_of(&arg, args.next())?.parse()?,
+                "--animation" => {
+                    parsed.animation.duration = value_of(&arg, args.next())?.parse()?
+                }
+                "--easing" => {
+                    parsed.animation.easing = Easing::from_name(&value_of(&arg, args.next())?)?
+                }
+                "--headless" => parsed.headless = true,
+                _ => return Err(format!("unknown argument '{}'", arg).into()),
+            }
+        }
+        if parsed.tick <= 0.0 || !parsed.tick.is_finite() {
+            return Err("--tick should be positive".into());
+        }
+        let animation = parsed.animation.duration;
+        if animation < 0.0 || !animation.is_finite() {
+            return Err("--animation should be 0 or positive".into());
+        }
+        if parsed.headless && parsed.replay.is_none() {
+            return Err("--headless requires --replay".into());
+        }
+        if parsed.world.is_some() && parsed.replay.is_some() {
+            return Err(
+                "--world can't be used with --replay, which starts from its own world".into(),
+            );
+        }
+        Ok(parsed)
+    }
+}
+
+fn value_of(arg: &str, value: Option<String>) -> Result<String, AnyError> {
+    value.ok_or_else(|| format!("missing value for {}", arg).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, AnyError> {
+        Args::parse(args.iter().map(|arg| arg.to_string()), 7)
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse(&["--seed", "12", "--replay", "bug.json", "--headless"]).unwrap();
+        assert_eq!(args.seed, 12);
+        assert_eq!(args.replay.as_deref(), Some("bug.json"));
+        assert!(args.headless);
+        assert_eq!(parse(&[]).unwrap().seed, 7);
+        assert_eq!(parse(&[]).unwrap().mode, WorldMode::Dream);
+        assert_eq!(parse(&["--lucid"]).unwrap().mode, WorldMode::Lucid);
+        assert_eq!(
+            parse(&["--world", "w.json"]).unwrap().mode,
+            WorldMode::Lucid
+        );
+        let args = parse(&["--tick", "0.5", "--turn-based"]).unwrap();
+        assert_eq!(args.tick, 0.5);
+        assert_eq!(args.pacing, Pacing::TurnBased);
+        assert_eq!(parse(&["--margin", "0"]).unwrap().margin, 0);
+        let args = parse(&["--animation", "0", "--easing", "linear"]).unwrap();
+        assert_eq!(args.animation.duration, 0.0);
+        assert_eq!(args.animation.easing, Easing::Linear);
+        let args = parse(&["--tileset", "art/tiles.json", "--theme", "dark.json"]).unwrap();
+        assert_eq!(args.tileset.as_deref(), Some("art/tiles.json"));
+        assert_eq!(args.theme.as_deref(), Some("dark.json"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&["--seed"]).is_err());
+        assert!(parse(&["--seed", "many"]).is_err());
+        assert!(parse(&["--headless"]).is_err());
+        assert!(parse(&["--replay", "bug.json", "--world", "w.json"]).is_err());
+        assert!(parse(&["--tick", "0"]).is_err());
+        assert!(parse(&["--animation", "-0.1"]).is_err());
+        assert!(parse(&["--animation", "NaN"]).is_err());
+        assert!(parse(&["--easing", "bouncy"]).is_err());
+        assert!(parse(&["--fast"]).is_err());
+    }
+}
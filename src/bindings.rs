@@ -0,0 +1,208 @@
+use crate::game::Action;
+use crate::repeat::RepeatConfig;
+use crate::versioned::load_json;
+use crate::AnyError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_BINDINGS_PATH: &str = "dream_maze_keys.json";
+const BINDINGS_VERSION: u32 = 3;
+
+/// Something the player can trigger with a key. Most of them are game actions, but quitting and
+/// the debug info are handled by the window.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Command {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Attack,
+    Wait,
+    Pause,
+    Quit,
+    Debug,
+}
+
+impl Command {
+    pub const ALL: [Command; 9] = [
+        Command::MoveUp,
+        Command::MoveDown,
+        Command::MoveLeft,
+        Command::MoveRight,
+        Command::Attack,
+        Command::Wait,
+        Command::Pause,
+        Command::Quit,
+        Command::Debug,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::MoveUp => "Move up",
+            Command::MoveDown => "Move down",
+            Command::MoveLeft => "Move left",
+            Command::MoveRight => "Move right",
+            Command::Attack => "Attack",
+            Command::Wait => "Wait a turn",
+            Command::Pause => "Pause",
+            Command::Quit => "Quit",
+            Command::Debug => "Debug info",
+        }
+    }
+
+    pub fn action(self) -> Option<Action> {
+        match self {
+            Command::MoveUp => Some(Action::MoveUp),
+            Command::MoveDown => Some(Action::MoveDown),
+            Command::MoveLeft => Some(Action::MoveLeft),
+            Command::MoveRight => Some(Action::MoveRight),
+            Command::Attack => Some(Action::Attack),
+            Command::Wait => Some(Action::Wait),
+            Command::Pause => Some(Action::Pause),
+            Command::Quit | Command::Debug => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Preset {
+    Arrows,
+    Wasd,
+    Vim,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Arrows, Preset::Wasd, Preset::Vim];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Arrows => "arrow keys",
+            Preset::Wasd => "WASD",
+            Preset::Vim => "vim keys (hjkl)",
+        }
+    }
+}
+
+/// Which keys trigger each command. Keys are named like macroquad's `KeyCode` variants, e.g.
+/// "Up", "W" or "Space", so that this doesn't depend on the window.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Bindings {
+    pub version: u32,
+    keys: BTreeMap<Command, Vec<String>>,
+    /// Holding a movement key keeps moving.
+    #[serde(default)]
+    pub repeat: RepeatConfig,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self::preset(Preset::Arrows)
+    }
+}
+
+impl Bindings {
+    pub fn preset(preset: Preset) -> Self {
+        let [up, down, left, right, attack] = match preset {
+            Preset::Arrows => ["Up", "Down", "Left", "Right", "X"],
+            Preset::Wasd => ["W", "S", "A", "D", "F"],
+            Preset::Vim => ["K", "J", "H", "L", "X"],
+        };
+        let keys = [
+            (Command::MoveUp, up),
+            (Command::MoveDown, down),
+            (Command::MoveLeft, left),
+            (Command::MoveRight, right),
+            (Command::Attack, attack),
+            (Command::Wait, "Z"),
+            (Command::Pause, "Space"),
+            (Command::Quit, "Escape"),
+            (Command::Debug, "F3"),
+        ];
+        Self {
+            version: BINDINGS_VERSION,
+            keys: keys
+                .into_iter()
+                .map(|(command, key)| (command, vec![key.to_string()]))
+                .collect(),
+            repeat: RepeatConfig::default(),
+        }
+    }
+
+    pub fn keys(&self, command: Command) -> &[String] {
+        self.keys.get(&command).map_or(&[], |keys| keys.as_slice())
+    }
+
+    /// Makes `key` the only key of `command`. If another command used that key, it gets the
+    /// previous keys of `command` instead, so that no command is left without a key.
+    pub fn bind(&mut self, command: Command, key: &str) {
+        let previous = self.keys.insert(command, vec![key.to_string()]);
+        for (other, keys) in self.keys.iter_mut() {
+            if *other != command && keys.iter().any(|k| k == key) {
+                keys.retain(|k| k != key);
+                if keys.is_empty() {
+                    *keys = previous.clone().unwrap_or_default();
+                }
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), AnyError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, AnyError> {
+        let bindings: Bindings = load_json(path, "key bindings", BINDINGS_VERSION)?;
+        if let Some(command) = Command::ALL.iter().find(|c| bindings.keys(**c).is_empty()) {
+            return Err(format!("there is no key for {}", command.name()).into());
+        }
+        if bindings.repeat.delay < 0.0 || bindings.repeat.interval <= 0.0 {
+            return Err("the key repeat delay and interval should be positive".into());
+        }
+        Ok(bindings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    #[test]
+    fn test_presets_bind_every_command() {
+        for preset in Preset::ALL {
+            let bindings = Bindings::preset(preset);
+            for command in Command::ALL {
+                assert_eq!(bindings.keys(command).len(), 1);
+            }
+        }
+        assert_eq!(Bindings::preset(Preset::Vim).keys(Command::MoveLeft), ["H"]);
+        assert_eq!(Bindings::default().keys(Command::Pause), ["Space"]);
+    }
+
+    #[test]
+    fn test_bind_swaps_keys() {
+        let mut bindings = Bindings::default();
+        bindings.bind(Command::MoveUp, "W");
+        assert_eq!(bindings.keys(Command::MoveUp), ["W"]);
+
+        bindings.bind(Command::MoveUp, "Space");
+        assert_eq!(bindings.keys(Command::MoveUp), ["Space"]);
+        assert_eq!(bindings.keys(Command::Pause), ["W"]);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut bindings = Bindings::preset(Preset::Wasd);
+        bindings.bind(Command::Quit, "Q");
+        let path = &temp_path("keys.json");
+        bindings.save(path).unwrap();
+        let loaded = Bindings::load(path).unwrap();
+
+        std::fs::write(path, r#"{"version": 3, "keys": {"MoveUp": ["W"]}}"#).unwrap();
+        let incomplete = Bindings::load(path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, bindings);
+        assert!(incomplete.is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::map::{Coord, Coord2};
+use glam::Vec2;
+
+/// Size of a tile on the screen at zoom 1, in pixels.
+pub const BASE_TILE_SIZE: f32 = 32.0;
+/// Tiles simulated beyond each edge of the screen, so that monsters keep moving a bit out of
+/// sight and the terrain is generated before it's visible.
+pub const DEFAULT_MARGIN: Coord = 4;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+/// Zoom factor of each step of the mouse wheel.
+const ZOOM_STEP: f32 = 1.25;
+
+/// Decides how big the simulated map is and which part of it is on the screen.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Camera {
+    pub zoom: f32,
+    pub margin: Coord,
+}
+
+/// The part of the map that is on the screen during a frame. The player is in its centre.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Viewport {
+    /// Map position of the top left tile on the screen.
+    pub first: Coord2,
+    /// Number of visible tiles.
+    pub tiles: Coord2,
+    pub tile_size: Vec2,
+}
+
+impl Camera {
+    pub fn new(margin: Coord) -> Self {
+        Self { zoom: 1.0, margin }
+    }
+
+    /// Zooms in for positive `steps` and out for negative ones.
+    pub fn zoom_by(&mut self, steps: f32) {
+        self.zoom = (self.zoom * ZOOM_STEP.powf(steps)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    pub fn tile_size(&self) -> Vec2 {
+        Vec2::splat(BASE_TILE_SIZE * self.zoom)
+    }
+
+    /// How many tiles fit in a window of `window` pixels, at least one.
+    pub fn visible_tiles(&self, window: Vec2) -> Coord2 {
+        let tiles = window / self.tile_size();
+        Coord2::new(tiles.x as Coord, tiles.y as Coord).max(Coord2::ONE)
+    }
+
+    /// Size that the map should have for a window of `window` pixels. It doesn't depend on the
+    /// zoom, so zooming never changes the simulation. Zooming out shows the margin.
+    pub fn map_tiles(&self, window: Vec2) -> Coord2 {
+        Camera::new(self.margin).visible_tiles(window) + Coord2::splat(self.margin * 2)
+    }
+
+    /// What to draw of a map of `map_size` tiles with the player at `player`. The map may be
+    /// smaller than the window, e.g. while playing back a replay recorded in a smaller window.
+    pub fn viewport(&self, window: Vec2, map_size: Coord2, player: Coord2) -> Viewport {
+        let tiles = self.visible_tiles(window).min(map_size);
+        Viewport {
+            first: player - tiles / 2,
+            tiles,
+            tile_size: self.tile_size(),
+        }
+    }
+}
+
+impl Viewport {
+    pub fn contains(&self, pos: Coord2) -> bool {
+        pos.cmpge(self.first).all() && pos.cmplt(self.first + self.tiles).all()
+    }
+
+    /// Top left corner of the map tile `pos` on the screen. Only meaningful if it's visible.
+    pub fn to_pixel(self, pos: Coord2) -> Vec2 {
+        self.pixel_at(pos.as_vec2())
+    }
+
+    /// Like `to_pixel`, for something between tiles.
+    pub fn pixel_at(self, pos: Vec2) -> Vec2 {
+        (pos - self.first.as_vec2()) * self.tile_size
+    }
+
+    /// The visible map tile under `pixel`, if any.
+    pub fn to_tile(self, pixel: Vec2) -> Option<Coord2> {
+        let tile = (pixel / self.tile_size).floor();
+        if tile.cmplt(Vec2::ZERO).any() || tile.cmpge(self.tiles.as_vec2()).any() {
+            None
+        } else {
+            Some(self.first + tile.as_uvec2())
+        }
+    }
+
+    /// Bottom right corner of the visible map on the screen.
+    pub fn end(&self) -> Vec2 {
+        self.tiles.as_vec2() * self.tile_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Vec2 = Vec2::new(320.0, 224.0);
+
+    #[test]
+    fn test_map_is_bigger_than_the_view() {
+        let camera = Camera::new(DEFAULT_MARGIN);
+        assert_eq!(camera.visible_tiles(WINDOW), Coord2::new(10, 7));
+        let map_tiles = camera.map_tiles(WINDOW);
+        assert_eq!(map_tiles, Coord2::new(18, 15));
+
+        let viewport = camera.viewport(WINDOW, map_tiles, map_tiles / 2);
+        assert_eq!(viewport.first, Coord2::new(4, 4));
+        assert!(viewport.contains(map_tiles / 2));
+        assert!(!viewport.contains(Coord2::new(3, 7)));
+        assert_eq!(viewport.to_pixel(Coord2::new(5, 4)), Vec2::new(32.0, 0.0));
+        assert_eq!(
+            viewport.to_tile(Vec2::new(40.0, 10.0)),
+            Some(Coord2::new(5, 4))
+        );
+        assert_eq!(viewport.to_tile(Vec2::new(330.0, 10.0)), None);
+
+        let small_map = camera.viewport(WINDOW, Coord2::new(5, 5), Coord2::new(2, 2));
+        assert_eq!(small_map.first, Coord2::new(0, 0));
+        assert_eq!(small_map.tiles, Coord2::new(5, 5));
+    }
+
+    #[test]
+    fn test_zoom() {
+        let mut camera = Camera::new(0);
+        camera.zoom_by(-1.0);
+        assert_eq!(camera.visible_tiles(WINDOW), Coord2::new(12, 8));
+        assert_eq!(camera.map_tiles(WINDOW), Coord2::new(10, 7));
+        camera.zoom_by(100.0);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+        assert_eq!(camera.visible_tiles(WINDOW), Coord2::new(2, 1));
+        camera.zoom_by(-100.0);
+        assert_eq!(camera.zoom, MIN_ZOOM);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::map::{tile_from_random, CoordDiff, CoordDiff2, Tile, TileDensity};
+use crate::rng::hash_position;
+use crate::versioned::{check_version, load_json};
+use crate::AnyError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const CHUNK_SIZE: CoordDiff = 16;
+const WORLD_VERSION: u32 = 1;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct ChunkCoord {
+    pub x: CoordDiff,
+    pub y: CoordDiff,
+}
+
+impl ChunkCoord {
+    pub fn containing(world_pos: CoordDiff2) -> Self {
+        Self {
+            x: world_pos.x.div_euclid(CHUNK_SIZE),
+            y: world_pos.y.div_euclid(CHUNK_SIZE),
+        }
+    }
+    pub fn origin(self) -> CoordDiff2 {
+        CoordDiff2::new(self.x * CHUNK_SIZE, self.y * CHUNK_SIZE)
+    }
+}
+
+/// Square of `CHUNK_SIZE` tiles. Serialized as one string per row, using `Tile::to_char`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(into = "Vec<String>", try_from = "Vec<String>")]
+pub struct Chunk {
+    tiles: Vec<Tile>,
+}
+
+impl Chunk {
+    fn index(world_pos: CoordDiff2) -> usize {
+        let x = world_pos.x.rem_euclid(CHUNK_SIZE);
+        let y = world_pos.y.rem_euclid(CHUNK_SIZE);
+        (y * CHUNK_SIZE + x) as usize
+    }
+}
+
+impl From<Chunk> for Vec<String> {
+    fn from(chunk: Chunk) -> Self {
+        chunk
+            .tiles
+            .chunks(CHUNK_SIZE as usize)
+            .map(|row| row.iter().map(|tile| tile.to_char()).collect())
+            .collect()
+    }
+}
+
+impl TryFrom<Vec<String>> for Chunk {
+    type Error = String;
+
+    fn try_from(rows: Vec<String>) -> Result<Self, Self::Error> {
+        let mut tiles = Vec::new();
+        for row in &rows {
+            for c in row.chars() {
+                tiles.push(Tile::from_char(c).ok_or_else(|| format!("unknown tile '{}'", c))?);
+            }
+        }
+        if tiles.len() != (CHUNK_SIZE * CHUNK_SIZE) as usize {
+            return Err(format!(
+                "a chunk should have {}x{} tiles",
+                CHUNK_SIZE, CHUNK_SIZE
+            ));
+        }
+        Ok(Self { tiles })
+    }
+}
+
+/// The infinite world of the lucid mode. Chunks are generated from the seed the first time
+/// they are needed, and from then on they remember any change.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(into = "WorldFile", try_from = "WorldFile")]
+pub struct ChunkStore {
+    seed: u64,
+    density: TileDensity,
+    chunks: HashMap<ChunkCoord, Chunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WorldFile {
+    version: u32,
+    seed: u64,
+    density: TileDensity,
+    chunks: Vec<(ChunkCoord, Chunk)>,
+}
+
+impl ChunkStore {
+    pub fn new(seed: u64, density: TileDensity) -> Self {
+        Self {
+            seed,
+            density,
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, world_pos: CoordDiff2) -> Tile {
+        self.chunk(world_pos).tiles[Chunk::index(world_pos)]
+    }
+
+    pub fn set(&mut self, world_pos: CoordDiff2, tile: Tile) {
+        self.chunk(world_pos).tiles[Chunk::index(world_pos)] = tile;
+    }
+
+    fn chunk(&mut self, world_pos: CoordDiff2) -> &mut Chunk {
+        let (seed, density) = (self.seed, self.density);
+        self.chunks
+            .entry(ChunkCoord::containing(world_pos))
+            .or_insert_with_key(|coord| generate_chunk(seed, density, *coord))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), AnyError> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, AnyError> {
+        load_json(path, "world", WORLD_VERSION)
+    }
+}
+
+impl From<ChunkStore> for WorldFile {
+    fn from(store: ChunkStore) -> Self {
+        let mut chunks = store.chunks.into_iter().collect::<Vec<_>>();
+        chunks.sort_by_key(|(coord, _)| *coord);
+        WorldFile {
+            version: WORLD_VERSION,
+            seed: store.seed,
+            density: store.density,
+            chunks,
+        }
+    }
+}
+
+impl TryFrom<WorldFile> for ChunkStore {
+    type Error = String;
+
+    fn try_from(world: WorldFile) -> Result<Self, Self::Error> {
+        // also checked here because worlds are embedded in saved games
+        check_version("world", world.version, WORLD_VERSION)?;
+        Ok(Self {
+            seed: world.seed,
+            density: world.density,
+            chunks: world.chunks.into_iter().collect(),
+        })
+    }
+}
+
+fn generate_chunk(seed: u64, density: TileDensity, coord: ChunkCoord) -> Chunk {
+    let origin = coord.origin();
+    let mut tiles = Vec::new();
+    for y in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let random = hash_position(seed, origin.x + x, origin.y + y);
+            tiles.push(tile_from_random(random, density));
+        }
+    }
+    Chunk { tiles }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    const DENSITY: TileDensity = TileDensity {
+        wall: 49,
+        coin: 2,
+        monster: 2,
+        health: 0,
+    };
+
+    #[test]
+    fn test_chunk_coord() {
+        let coord = |x, y| ChunkCoord::containing(CoordDiff2::new(x, y));
+        assert_eq!(coord(0, 15), ChunkCoord { x: 0, y: 0 });
+        assert_eq!(coord(16, -1), ChunkCoord { x: 1, y: -1 });
+        assert_eq!(coord(-16, -17), ChunkCoord { x: -1, y: -2 });
+        assert_eq!(Chunk::index(CoordDiff2::new(-1, -16)), 15);
+    }
+
+    #[test]
+    fn test_generation_does_not_depend_on_order() {
+        let mut a = ChunkStore::new(5, DENSITY);
+        let mut b = ChunkStore::new(5, DENSITY);
+        let positions = [
+            CoordDiff2::new(-40, 3),
+            CoordDiff2::new(0, 0),
+            CoordDiff2::new(17, -200),
+        ];
+        let from_a = positions.map(|pos| a.get(pos));
+        b.get(CoordDiff2::new(1000, 1000));
+        for pos in positions.iter().rev() {
+            b.get(*pos);
+        }
+        assert_eq!(positions.map(|pos| b.get(pos)), from_a);
+        assert_eq!(a.chunks.len(), 3);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut world = ChunkStore::new(5, DENSITY);
+        world.set(CoordDiff2::new(-3, 20), Tile::Exit);
+        world.get(CoordDiff2::new(100, 100));
+        let path = &temp_path("world.json");
+        world.save(path).unwrap();
+        let loaded = ChunkStore::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, world);
+    }
+}
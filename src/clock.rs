@@ -0,0 +1,59 @@
+/// Duration of a simulation tick in seconds. The game was tuned at 60 frames per second.
+pub const DEFAULT_TICK: f32 = 1.0 / 60.0;
+/// After a very long frame (e.g. while the window was being dragged) the simulation skips ahead
+/// instead of trying to catch up all at once.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+/// Turns the real time between frames into a whole number of simulation ticks, so that the game
+/// runs at the same speed at any frame rate.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Clock {
+    tick: f32,
+    /// Time not yet simulated, always less than a tick.
+    accumulated: f32,
+}
+
+impl Clock {
+    pub fn new(tick: f32) -> Self {
+        Self {
+            tick,
+            accumulated: 0.0,
+        }
+    }
+
+    /// How many ticks to simulate after a frame that lasted `delta` seconds.
+    pub fn update(&mut self, delta: f32) -> u32 {
+        self.accumulated += delta;
+        let ticks = (self.accumulated / self.tick) as u32;
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.accumulated = 0.0;
+            MAX_TICKS_PER_FRAME
+        } else {
+            self.accumulated -= ticks as f32 * self.tick;
+            ticks
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticks_do_not_depend_on_frame_rate() {
+        // powers of two, for the same reason as `CONFIG` in the repeat tests
+        let count_ticks = |frame_time: f32, frames: u32| {
+            let mut clock = Clock::new(1.0 / 64.0);
+            (0..frames).map(|_| clock.update(frame_time)).sum::<u32>()
+        };
+        assert_eq!(count_ticks(1.0 / 64.0, 64), 64);
+        assert_eq!(count_ticks(1.0 / 128.0, 128), 64);
+        assert_eq!(count_ticks(1.0 / 32.0, 32), 64);
+
+        let mut clock = Clock::new(0.25);
+        assert_eq!(clock.update(0.125), 0);
+        assert_eq!(clock.update(0.125), 1);
+        assert_eq!(clock.update(10.0), MAX_TICKS_PER_FRAME);
+        assert_eq!(clock.update(0.125), 0);
+    }
+}
@@ -0,0 +1,721 @@
+use crate::chunk::ChunkStore;
+use crate::level::{LevelConfig, CAMPAIGN};
+use crate::map::{
+    to_signed, Coord, Coord2, CoordDiff, CoordDiff2, Hit, Map, Tile, WorldMode, DOWN, LEFT, RIGHT,
+    UP,
+};
+use crate::rng::Rng;
+use crate::AnyError;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_HEALTH: f32 = 5.0;
+/// Ticks between two attacks.
+pub const ATTACK_COOLDOWN_TICKS: i32 = 30;
+
+/// Everything the player (or a bot) can ask the game to do in a single step.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Idle,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    /// Hits the monsters around the player, see `Map::attack`.
+    Attack,
+    /// Lets the monsters move without moving the player, in turn-based games.
+    Wait,
+    Pause,
+    Restart,
+    NextLevel,
+    /// The window now fits this many tiles. Recorded like any other action, so that replays
+    /// resize the map at the same moment.
+    Resize(Coord2),
+}
+
+impl Action {
+    pub fn direction(self) -> Option<CoordDiff2> {
+        match self {
+            Action::MoveUp => Some(UP),
+            Action::MoveDown => Some(DOWN),
+            Action::MoveLeft => Some(LEFT),
+            Action::MoveRight => Some(RIGHT),
+            Action::Idle
+            | Action::Attack
+            | Action::Wait
+            | Action::Pause
+            | Action::Restart
+            | Action::NextLevel
+            | Action::Resize(_) => None,
+        }
+    }
+
+    pub fn from_direction(dir: CoordDiff2) -> Option<Action> {
+        match dir {
+            UP => Some(Action::MoveUp),
+            DOWN => Some(Action::MoveDown),
+            LEFT => Some(Action::MoveLeft),
+            RIGHT => Some(Action::MoveRight),
+            _ => None,
+        }
+    }
+}
+
+/// When the monsters move.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Pacing {
+    /// Every `LevelConfig::monster_period_ticks` ticks.
+    #[default]
+    RealTime,
+    /// Once after each successful move of the player or `Action::Wait`. If a monster is then on
+    /// the player's tile, the player loses one point of health.
+    TurnBased,
+}
+
+/// What happened during a step, so that the caller can react (sounds, logs, bots) without
+/// diffing the state.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    Moved(CoordDiff2),
+    Blocked(CoordDiff2),
+    /// In world coordinates.
+    MonsterMoved {
+        from: CoordDiff2,
+        to: CoordDiff2,
+    },
+    Damaged {
+        health: f32,
+    },
+    Healed {
+        health: f32,
+    },
+    Attacked,
+    MonsterHit(Hit),
+    Died,
+    CoinCollected {
+        score: u32,
+    },
+    DoorPartCollected {
+        collected: i32,
+    },
+    ExitOpened,
+    Won,
+    Paused,
+    Resumed,
+    Restarted,
+    LevelStarted {
+        level: u32,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct GameState {
+    pub seed: u64,
+    rng: Rng,
+    pub mode: WorldMode,
+    pub player_health: f32,
+    pub map: Map,
+    pub doors_parts_collected: i32,
+    pub score: u32,
+    pub accumulated_pos: CoordDiff2,
+    pub next_door: CoordDiff2,
+    /// Appears once all the door parts are collected. Reaching it completes the level.
+    pub exit: Option<CoordDiff2>,
+    pub escaped: bool,
+    pub level: u32,
+    pub paused: bool,
+    /// Number of steps simulated since the level started.
+    pub tick: u64,
+    #[serde(default)]
+    pub pacing: Pacing,
+    /// Tiles of the map simulated beyond each edge of the screen. Door parts and the exit are only
+    /// placed inside them, where the player can see them.
+    #[serde(default)]
+    pub margin: Coord,
+    /// Ticks left until the player can attack again, see `ATTACK_COOLDOWN_TICKS`.
+    #[serde(default)]
+    pub attack_cooldown: i32,
+    /// The lucid world is the one of a --world file, so restarts and new levels stay in it
+    /// instead of generating another one.
+    pub persistent_world: bool,
+}
+
+impl GameState {
+    /// The first level, without margin.
+    #[cfg(test)]
+    pub fn new(screen_tiles: Coord2, player: Coord2, seed: u64, mode: WorldMode) -> Self {
+        Self::new_at_level(screen_tiles, player, seed, mode, 0, 0)
+    }
+
+    /// The same seed and the same sequence of actions always produce the same game. `level` is
+    /// an index into `CAMPAIGN`.
+    pub fn new_at_level(
+        screen_tiles: Coord2,
+        player: Coord2,
+        seed: u64,
+        mode: WorldMode,
+        level: u32,
+        margin: Coord,
+    ) -> Self {
+        Self::with_world(screen_tiles, player, seed, mode, level, margin, None)
+    }
+
+    /// Like `new_at_level`, but in lucid mode staying in `world` if given, see
+    /// `persistent_world`.
+    fn with_world(
+        screen_tiles: Coord2,
+        player: Coord2,
+        seed: u64,
+        mode: WorldMode,
+        level: u32,
+        margin: Coord,
+        world: Option<ChunkStore>,
+    ) -> Self {
+        let config = &CAMPAIGN[level as usize];
+        let mut rng = Rng::new(seed);
+        let (map_seed, density) = (rng.next_u64(), config.density);
+        let persistent_world = world.is_some();
+        let map = match world {
+            None => Map::new(screen_tiles, player, map_seed, density, mode),
+            world => Map::with_world(screen_tiles, player, map_seed, density, world),
+        };
+        let accumulated_pos = CoordDiff2::new(0, 0);
+        let mut game_state = Self {
+            seed,
+            rng,
+            mode,
+            player_health: config.starting_health,
+            map,
+            doors_parts_collected: 0,
+            score: 0,
+            accumulated_pos,
+            next_door: accumulated_pos,
+            exit: None,
+            escaped: false,
+            level,
+            paused: false,
+            tick: 0,
+            pacing: Pacing::RealTime,
+            margin,
+            attack_cooldown: 0,
+            persistent_world,
+        };
+        game_state.next_door = game_state.calculate_rand_accumulated_pos();
+        game_state
+    }
+
+    /// Applies one action. `Pause`, `Restart`, `NextLevel` and `Resize` take effect immediately;
+    /// any other action also advances the simulation by one tick, unless the game is paused or
+    /// over.
+    pub fn step(&mut self, action: Action) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        match action {
+            Action::Pause => {
+                self.paused = !self.paused;
+                events.push(if self.paused {
+                    GameEvent::Paused
+                } else {
+                    GameEvent::Resumed
+                });
+                return events;
+            }
+            Action::Restart => {
+                let (seed, pacing) = (self.rng.next_u64(), self.pacing);
+                let (size, player, mode) = (self.map.size(), self.map.player, self.mode);
+                let world = self.kept_world();
+                *self = GameState::with_world(size, player, seed, mode, 0, self.margin, world);
+                self.pacing = pacing;
+                events.push(GameEvent::Restarted);
+                return events;
+            }
+            Action::NextLevel => {
+                if self.has_won() && !self.is_last_level() {
+                    self.next_level();
+                    events.push(GameEvent::LevelStarted { level: self.level });
+                }
+                return events;
+            }
+            Action::Resize(screen_tiles) => {
+                self.map.resize(screen_tiles);
+                self.show_exit();
+                return events;
+            }
+            _ => {}
+        }
+        if self.paused || self.is_over() {
+            return events;
+        }
+        self.attack_cooldown = 0.max(self.attack_cooldown - 1);
+        if action == Action::Attack && self.attack_cooldown == 0 {
+            self.attack_cooldown = ATTACK_COOLDOWN_TICKS;
+            events.push(GameEvent::Attacked);
+            events.extend(self.map.attack().into_iter().map(GameEvent::MonsterHit));
+        }
+        let mut moved = false;
+        if let Some(dir) = action.direction() {
+            match self.map.move_to(dir) {
+                Some(stepped_on) => {
+                    moved = true;
+                    self.accumulated_pos += dir;
+                    events.push(GameEvent::Moved(dir));
+                    if stepped_on == Tile::Coin {
+                        self.score += 1;
+                        events.push(GameEvent::CoinCollected { score: self.score });
+                    } else if stepped_on == Tile::Health {
+                        self.player_health = MAX_HEALTH.min(self.player_health + 1.0);
+                        events.push(GameEvent::Healed {
+                            health: self.player_health,
+                        });
+                    }
+                    // an exit tile alone isn't enough, e.g. one left in an old --world file
+                    if self.exit == Some(self.accumulated_pos) {
+                        self.escaped = true;
+                        events.push(GameEvent::Won);
+                    }
+                }
+                None => events.push(GameEvent::Blocked(dir)),
+            }
+        }
+
+        let mut monster_on_player = self.map.monster_at(self.map.player).is_some();
+        let monsters_move = match self.pacing {
+            Pacing::RealTime => (self.tick + 1).is_multiple_of(self.config().monster_period_ticks),
+            Pacing::TurnBased => moved || action == Action::Wait,
+        };
+        if monsters_move {
+            let moves = self.map.advance();
+            events.extend(
+                moves
+                    .into_iter()
+                    .map(|(from, to)| GameEvent::MonsterMoved { from, to }),
+            );
+            if self.pacing == Pacing::TurnBased {
+                monster_on_player = self.map.monster_at(self.map.player).is_some();
+            }
+            if monster_on_player {
+                self.player_health = 0.0_f32.max(self.player_health - 1.0);
+                events.push(GameEvent::Damaged {
+                    health: self.player_health,
+                });
+                if self.is_dead() {
+                    events.push(GameEvent::Died);
+                }
+            }
+        }
+        let door_count = self.config().door_count;
+        if self.doors_parts_collected < door_count && self.accumulated_pos == self.next_door {
+            self.doors_parts_collected += 1;
+            events.push(GameEvent::DoorPartCollected {
+                collected: self.doors_parts_collected,
+            });
+            if self.doors_parts_collected < door_count {
+                self.next_door = self.calculate_rand_accumulated_pos();
+            } else {
+                self.exit = Some(self.calculate_rand_accumulated_pos());
+                events.push(GameEvent::ExitOpened);
+            }
+        }
+        self.show_exit();
+        self.tick += 1;
+        events
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.player_health <= 0.0
+    }
+    pub fn has_won(&self) -> bool {
+        self.escaped
+    }
+    pub fn is_over(&self) -> bool {
+        self.is_dead() || self.has_won()
+    }
+
+    pub fn config(&self) -> &'static LevelConfig {
+        &CAMPAIGN[self.level as usize]
+    }
+    pub fn is_last_level(&self) -> bool {
+        self.level as usize + 1 >= CAMPAIGN.len()
+    }
+
+    /// Where a position in world coordinates (like `next_door`) is on the screen, if visible.
+    pub fn to_screen(&self, pos: CoordDiff2) -> Option<Coord2> {
+        let screen_pos = pos - self.accumulated_pos + to_signed(self.map.player);
+        let size = self.map.size();
+        if screen_pos.x >= 0
+            && screen_pos.x < size.x as CoordDiff
+            && screen_pos.y >= 0
+            && screen_pos.y < size.y as CoordDiff
+        {
+            Some(Coord2::new(screen_pos.x as Coord, screen_pos.y as Coord))
+        } else {
+            None
+        }
+    }
+
+    fn next_level(&mut self) {
+        let seed = self.rng.next_u64();
+        let (score, pacing) = (self.score, self.pacing);
+        *self = GameState::with_world(
+            self.map.size(),
+            self.map.player,
+            seed,
+            self.mode,
+            self.level + 1,
+            self.margin,
+            self.kept_world(),
+        );
+        self.score = score;
+        self.pacing = pacing;
+    }
+
+    /// Replaces the lucid world with a saved one. A door part or exit on screen that the new
+    /// terrain makes unreachable is moved.
+    pub fn load_world(&mut self, path: &str) -> Result<(), AnyError> {
+        self.map.load_world(path)?;
+        if self.is_out_of_reach(self.next_door) {
+            self.next_door = self.calculate_rand_accumulated_pos();
+        }
+        if self.exit.is_some_and(|exit| self.is_out_of_reach(exit)) {
+            self.exit = Some(self.calculate_rand_accumulated_pos());
+        }
+        Ok(())
+    }
+
+    fn is_out_of_reach(&self, pos: CoordDiff2) -> bool {
+        self.to_screen(pos).is_some_and(|pos| {
+            self.map.get(pos) == Tile::Wall || self.map.find_path(self.map.player, pos).is_none()
+        })
+    }
+
+    fn kept_world(&self) -> Option<ChunkStore> {
+        self.persistent_world.then(|| self.map.world()).flatten()
+    }
+
+    /// A random floor tile on the screen that the player can walk to, other than the player's.
+    /// The lucid world keeps its walls for good, so a door part or exit on a wall, or walled in,
+    /// could never be reached.
+    fn calculate_rand_accumulated_pos(&mut self) -> CoordDiff2 {
+        let (corner, visible) = visible_area(self.map.size(), self.margin);
+        let player = self.map.player;
+        let mut candidates = (0..visible.x * visible.y)
+            .map(|i| corner + Coord2::new(i % visible.x, i / visible.x))
+            .filter(|pos| *pos != player && self.map.get(*pos) == Tile::Floor)
+            .collect::<Vec<_>>();
+        while !candidates.is_empty() {
+            let i = self.rng.rand() as usize % candidates.len();
+            let pos = candidates.swap_remove(i);
+            if self.map.find_path(player, pos).is_some() {
+                return to_signed(pos) - to_signed(player) + self.accumulated_pos;
+            }
+        }
+        // the player is walled in, so make room anywhere on the screen
+        loop {
+            let pos = calculate_rand_accumulated_pos(
+                &mut self.rng,
+                self.accumulated_pos,
+                player,
+                self.map.size(),
+                self.margin,
+            );
+            if let Some(screen_pos) = self.to_screen(pos).filter(|pos| *pos != player) {
+                self.map.set(screen_pos, Tile::Floor);
+                return pos;
+            }
+        }
+    }
+
+    /// The map forgets whatever leaves the view, so the exit is put back every time it's visible.
+    fn show_exit(&mut self) {
+        if let Some(pos) = self.exit.and_then(|exit| self.to_screen(exit)) {
+            self.map.set(pos, Tile::Exit);
+        }
+    }
+}
+
+fn calculate_rand_accumulated_pos(
+    rng: &mut Rng,
+    accumulated_pos: CoordDiff2,
+    player: Coord2,
+    screen_tiles: Coord2,
+    margin: Coord,
+) -> CoordDiff2 {
+    let (corner, visible) = visible_area(screen_tiles, margin);
+    let i = (rng.rand() % (visible.x * visible.y)) as i32;
+    let pos = scalar_to_around_accumulated_pos(accumulated_pos, player, visible, i);
+    pos + to_signed(corner)
+}
+
+/// The top left tile and the size of the part of the map inside the margins.
+fn visible_area(screen_tiles: Coord2, margin: Coord) -> (Coord2, Coord2) {
+    // at least one tile, even if the map is smaller than the margins
+    let margin = Coord2::splat(margin).min((screen_tiles - Coord2::ONE) / 2);
+    (margin, screen_tiles - margin * 2)
+}
+
+fn scalar_to_around_accumulated_pos(
+    accumulated_pos: CoordDiff2,
+    player: Coord2,
+    screen_tiles: Coord2,
+    i: i32,
+) -> CoordDiff2 {
+    let door_pos_unsigned = CoordDiff2::new(i % screen_tiles.x as i32, i / screen_tiles.x as i32);
+    let player = to_signed(player);
+    door_pos_unsigned - player + accumulated_pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monster::MonsterKind;
+
+    #[test]
+    fn test_generate_door() {
+        let screen_tiles = Coord2::new(4, 10);
+        assert_eq!(to_pos(0, screen_tiles), CoordDiff2::new(98, 195));
+        assert_eq!(to_pos(1, screen_tiles), CoordDiff2::new(99, 195));
+        assert_eq!(
+            to_pos((screen_tiles.x * screen_tiles.y) as i32 - 1, screen_tiles),
+            CoordDiff2::new(101, 204)
+        )
+    }
+
+    #[test]
+    fn test_doors_are_on_screen() {
+        let (size, margin) = (Coord2::new(17, 15), 4);
+        for seed in 0..50 {
+            let game_state =
+                GameState::new_at_level(size, size / 2, seed, WorldMode::Dream, 0, margin);
+            let door = game_state.to_screen(game_state.next_door).unwrap();
+            assert!(door.cmpge(Coord2::splat(margin)).all(), "{:?}", door);
+            assert!(door.cmplt(size - margin).all(), "{:?}", door);
+        }
+    }
+
+    #[test]
+    fn test_doors_are_reachable() {
+        let size = Coord2::new(17, 15);
+        for seed in 0..200 {
+            let game_state = GameState::new_at_level(size, size / 2, seed, WorldMode::Lucid, 0, 4);
+            let door = game_state.to_screen(game_state.next_door).unwrap();
+            assert_eq!(game_state.map.get(door), Tile::Floor, "seed {}", seed);
+            let path = game_state.map.find_path(game_state.map.player, door);
+            assert!(path.is_some(), "seed {}", seed);
+        }
+    }
+
+    fn new_game(mode: WorldMode) -> GameState {
+        GameState::new(Coord2::new(9, 7), Coord2::new(4, 3), 42000, mode)
+    }
+
+    fn to_pos(i: i32, screen_tiles: Coord2) -> CoordDiff2 {
+        scalar_to_around_accumulated_pos(
+            CoordDiff2::new(100, 200),
+            Coord2::new(2, 5),
+            screen_tiles,
+            i,
+        )
+    }
+
+    #[test]
+    fn test_pause_freezes_simulation() {
+        let mut game_state = new_game(WorldMode::Dream);
+        assert_eq!(game_state.step(Action::Pause), vec![GameEvent::Paused]);
+        assert!(game_state.step(Action::MoveDown).is_empty());
+        assert_eq!(game_state.tick, 0);
+        assert_eq!(game_state.accumulated_pos, CoordDiff2::new(0, 0));
+        assert_eq!(game_state.step(Action::Pause), vec![GameEvent::Resumed]);
+        game_state.step(Action::Idle);
+        assert_eq!(game_state.tick, 1);
+    }
+
+    #[test]
+    fn test_move_along_cleared_cross() {
+        let mut game_state = new_game(WorldMode::Dream);
+        // the row and column of the player are always walkable on a fresh map
+        let events = game_state.step(Action::MoveRight);
+        assert_eq!(events[0], GameEvent::Moved(RIGHT));
+        assert_eq!(game_state.accumulated_pos, RIGHT);
+    }
+
+    #[test]
+    fn test_attack_cooldown_and_health_pickup() {
+        let mut game_state = new_game(WorldMode::Dream);
+        assert_eq!(game_state.step(Action::Attack), vec![GameEvent::Attacked]);
+        for _ in 1..ATTACK_COOLDOWN_TICKS {
+            assert!(game_state.step(Action::Attack).is_empty());
+        }
+        assert_eq!(game_state.step(Action::Attack), vec![GameEvent::Attacked]);
+
+        game_state.player_health = MAX_HEALTH - 0.5;
+        game_state.map.set(Coord2::new(5, 3), Tile::Health);
+        let events = game_state.step(Action::MoveRight);
+        let healed = GameEvent::Healed { health: MAX_HEALTH };
+        assert_eq!(events, vec![GameEvent::Moved(RIGHT), healed]);
+        assert_eq!(game_state.map.get(game_state.map.player), Tile::Floor);
+    }
+
+    #[test]
+    fn test_turn_based_monsters_wait_for_the_player() {
+        let mut game_state = new_game(WorldMode::Dream);
+        game_state.pacing = Pacing::TurnBased;
+        let stalker = Tile::Monster(MonsterKind::Stalker);
+        game_state.map.set(Coord2::new(7, 3), stalker);
+        let monsters = game_state.map.monsters().to_vec();
+        for _ in 0..200 {
+            game_state.step(Action::Idle);
+        }
+        assert_eq!(game_state.map.monsters(), monsters);
+
+        game_state.step(Action::MoveRight);
+        let stalker_pos = game_state.map.monster_at(Coord2::new(5, 3)).map(|m| m.pos);
+        assert_eq!(stalker_pos, Some(CoordDiff2::new(2, 0)));
+        game_state.step(Action::Restart);
+        assert_eq!(game_state.pacing, Pacing::TurnBased);
+    }
+
+    #[test]
+    fn test_turn_based_wait_and_damage_per_turn() {
+        let mut game_state = new_game(WorldMode::Dream);
+        game_state.pacing = Pacing::TurnBased;
+        game_state.map.set(Coord2::new(4, 2), Tile::Wall);
+        game_state
+            .map
+            .set(Coord2::new(6, 3), Tile::Monster(MonsterKind::Stalker));
+        let without_moves = |events: Vec<GameEvent>| {
+            events
+                .into_iter()
+                .filter(|e| !matches!(e, GameEvent::MonsterMoved { .. }))
+                .collect::<Vec<_>>()
+        };
+        let stalker_moved = GameEvent::MonsterMoved {
+            from: CoordDiff2::new(2, 0),
+            to: CoordDiff2::new(1, 0),
+        };
+        assert!(game_state.step(Action::Wait).contains(&stalker_moved));
+        assert!(game_state.map.monster_at(Coord2::new(5, 3)).is_some());
+        assert_eq!(
+            game_state.step(Action::MoveUp),
+            vec![GameEvent::Blocked(UP)]
+        );
+        assert!(game_state.map.monster_at(Coord2::new(5, 3)).is_some());
+
+        let health = game_state.player_health;
+        for turn in 1..=2 {
+            let damaged = GameEvent::Damaged {
+                health: health - turn as f32,
+            };
+            assert_eq!(without_moves(game_state.step(Action::Wait)), vec![damaged]);
+        }
+    }
+
+    #[test]
+    fn test_only_the_open_exit_wins() {
+        let mut game_state = new_game(WorldMode::Lucid);
+        game_state.map.set(Coord2::new(5, 3), Tile::Exit);
+        assert!(!game_state.step(Action::MoveRight).contains(&GameEvent::Won));
+        assert!(!game_state.has_won());
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut game_state = new_game(WorldMode::Dream);
+        game_state.player_health = 0.0;
+        assert!(game_state.is_over());
+        assert!(game_state.step(Action::MoveRight).is_empty());
+        assert_eq!(game_state.step(Action::Restart), vec![GameEvent::Restarted]);
+        assert_eq!(game_state.player_health, MAX_HEALTH);
+        assert_eq!(game_state.map.size(), Coord2::new(9, 7));
+        assert_ne!(game_state.seed, 42000);
+    }
+
+    #[test]
+    fn test_persistent_world_survives_restart_and_levels() {
+        let mut game_state = new_game(WorldMode::Lucid);
+        game_state.persistent_world = true;
+        game_state.step(Action::MoveRight);
+        game_state.map.set(Coord2::new(0, 0), Tile::Coin);
+        game_state.step(Action::Restart);
+        // back at the start, so the coin is one tile further right
+        assert_eq!(game_state.map.get(Coord2::new(1, 0)), Tile::Coin);
+
+        game_state.map.set(Coord2::new(1, 0), Tile::Health);
+        game_state.escaped = true;
+        game_state.step(Action::NextLevel);
+        assert_eq!(game_state.level, 1);
+        assert_eq!(game_state.map.get(Coord2::new(1, 0)), Tile::Health);
+        assert!(game_state.persistent_world);
+    }
+
+    #[test]
+    fn test_exit_appears_after_doors_and_completes_level() {
+        let mut game_state = new_game(WorldMode::Dream);
+        game_state.doors_parts_collected = game_state.config().door_count - 1;
+        game_state.next_door = game_state.accumulated_pos + RIGHT;
+        game_state.map.set(Coord2::new(5, 3), Tile::Floor);
+        let events = game_state.step(Action::MoveRight);
+        assert!(events.contains(&GameEvent::ExitOpened));
+        assert!(!game_state.has_won());
+
+        let exit = game_state.exit.unwrap();
+        let exit_on_screen = game_state.to_screen(exit).unwrap();
+        assert_eq!(game_state.map.get(exit_on_screen), Tile::Exit);
+        assert_eq!(game_state.step(Action::NextLevel), vec![]);
+
+        // teleport the exit next to the player
+        game_state.map.set(exit_on_screen, Tile::Floor);
+        game_state.exit = Some(game_state.accumulated_pos + DOWN);
+        game_state.score = 3;
+        game_state.step(Action::Idle);
+        assert!(game_state.step(Action::MoveDown).contains(&GameEvent::Won));
+        assert!(game_state.has_won());
+
+        let events = game_state.step(Action::NextLevel);
+        assert_eq!(events, vec![GameEvent::LevelStarted { level: 1 }]);
+        assert_eq!(game_state.score, 3);
+        assert_eq!(game_state.exit, None);
+        assert!(!game_state.is_over());
+        assert_eq!(game_state.player_health, CAMPAIGN[1].starting_health);
+    }
+
+    #[test]
+    fn test_campaign_ends_at_last_level() {
+        let last = CAMPAIGN.len() as u32 - 1;
+        let mut game_state = GameState::new_at_level(
+            Coord2::new(9, 7),
+            Coord2::new(4, 3),
+            42000,
+            WorldMode::Dream,
+            last,
+            0,
+        );
+        assert!(game_state.is_last_level());
+        game_state.escaped = true;
+        assert_eq!(game_state.step(Action::NextLevel), vec![]);
+        assert_eq!(game_state.level, last);
+        game_state.step(Action::Restart);
+        assert_eq!(game_state.level, 0);
+    }
+
+    #[test]
+    fn test_same_seed_same_session() {
+        let actions = [
+            Action::MoveRight,
+            Action::MoveDown,
+            Action::Idle,
+            Action::MoveLeft,
+            Action::MoveUp,
+            Action::MoveUp,
+        ];
+        let play = |mode| {
+            let mut game_state = GameState::new(Coord2::new(9, 7), Coord2::new(4, 3), 1234, mode);
+            let mut events = Vec::new();
+            for _ in 0..20 {
+                for action in actions {
+                    events.extend(game_state.step(action));
+                }
+            }
+            (game_state, events)
+        };
+        assert_eq!(play(WorldMode::Dream), play(WorldMode::Dream));
+        assert_eq!(play(WorldMode::Lucid), play(WorldMode::Lucid));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::game::Action;
+
+/// How far the left stick has to be pushed to count as a move.
+const STICK_DEAD_ZONE: f32 = 0.5;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+pub enum GamepadButton {
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Start,
+    /// A on Xbox layouts, cross on PlayStation ones.
+    South,
+    /// X on Xbox layouts, square on PlayStation ones.
+    West,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+pub enum GamepadAxis {
+    LeftStickX,
+    /// Positive is up.
+    LeftStickY,
+}
+
+/// Controller input, independent of where it comes from, so that it can be synthesized in tests.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+pub enum GamepadEvent {
+    Pressed(GamepadButton),
+    Released(GamepadButton),
+    AxisChanged(GamepadAxis, f32),
+}
+
+/// State of the controllers, built from the events of each frame.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Gamepad {
+    down: Vec<GamepadButton>,
+    pressed: Vec<GamepadButton>,
+    stick: (f32, f32),
+    previous_stick_move: Option<Action>,
+    /// Becomes true with the first event, to show the focus of the menus only to gamepad users.
+    pub used: bool,
+}
+
+impl Gamepad {
+    /// Call this before handling the events of a new frame.
+    pub fn begin_frame(&mut self) {
+        self.pressed.clear();
+        self.previous_stick_move = self.stick_move();
+    }
+
+    pub fn handle(&mut self, event: GamepadEvent) {
+        self.used = true;
+        match event {
+            GamepadEvent::Pressed(button) => {
+                self.pressed.push(button);
+                self.down.push(button);
+            }
+            GamepadEvent::Released(button) => self.down.retain(|b| *b != button),
+            GamepadEvent::AxisChanged(GamepadAxis::LeftStickX, value) => self.stick.0 = value,
+            GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, value) => self.stick.1 = value,
+        }
+    }
+
+    pub fn is_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    /// The move being held with the D-pad, or else with the stick.
+    pub fn held_move(&self) -> Option<Action> {
+        self.down
+            .iter()
+            .rev()
+            .find_map(|button| dpad_move(*button))
+            .or_else(|| self.stick_move())
+    }
+
+    /// A move that started this frame, used to navigate the menus.
+    pub fn pressed_move(&self) -> Option<Action> {
+        let stick_move = self
+            .stick_move()
+            .filter(|m| Some(*m) != self.previous_stick_move);
+        self.pressed
+            .iter()
+            .find_map(|button| dpad_move(*button))
+            .or(stick_move)
+    }
+
+    fn stick_move(&self) -> Option<Action> {
+        let (x, y) = self.stick;
+        if x.abs().max(y.abs()) < STICK_DEAD_ZONE {
+            None
+        } else if x.abs() > y.abs() {
+            Some(if x > 0.0 {
+                Action::MoveRight
+            } else {
+                Action::MoveLeft
+            })
+        } else {
+            Some(if y > 0.0 {
+                Action::MoveUp
+            } else {
+                Action::MoveDown
+            })
+        }
+    }
+}
+
+fn dpad_move(button: GamepadButton) -> Option<Action> {
+    match button {
+        GamepadButton::DPadUp => Some(Action::MoveUp),
+        GamepadButton::DPadDown => Some(Action::MoveDown),
+        GamepadButton::DPadLeft => Some(Action::MoveLeft),
+        GamepadButton::DPadRight => Some(Action::MoveRight),
+        GamepadButton::Start | GamepadButton::South | GamepadButton::West => None,
+    }
+}
+
+/// Which button of a menu is selected with the gamepad.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Focus {
+    menu: &'static str,
+    index: usize,
+}
+
+impl Focus {
+    /// Moves the focus among the `count` buttons of `menu`, starting at the first one whenever
+    /// the menu changes. Returns the focused index if it was confirmed with `South`.
+    pub fn update(&mut self, menu: &'static str, count: usize, gamepad: &Gamepad) -> Option<usize> {
+        if self.menu != menu || self.index >= count {
+            *self = Self { menu, index: 0 };
+        }
+        if count == 0 {
+            return None;
+        }
+        match gamepad.pressed_move() {
+            Some(Action::MoveUp | Action::MoveLeft) => {
+                self.index = (self.index + count - 1) % count
+            }
+            Some(Action::MoveDown | Action::MoveRight) => self.index = (self.index + 1) % count,
+            _ => {}
+        }
+        gamepad
+            .is_pressed(GamepadButton::South)
+            .then_some(self.index)
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// Reads the real controllers. Needs the `gamepad` feature; without it there are no events.
+pub struct GamepadSource {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl GamepadSource {
+    pub fn open() -> Self {
+        Self {
+            #[cfg(feature = "gamepad")]
+            gilrs: gilrs::Gilrs::new()
+                .map_err(|e| eprintln!("gamepads are not available: {}", e))
+                .ok(),
+        }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        Vec::new()
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        use gilrs::{Axis, Button, EventType};
+        let mut events = Vec::new();
+        let Some(gilrs) = &mut self.gilrs else {
+            return events;
+        };
+        let to_button = |button: Button| match button {
+            Button::DPadUp => Some(GamepadButton::DPadUp),
+            Button::DPadDown => Some(GamepadButton::DPadDown),
+            Button::DPadLeft => Some(GamepadButton::DPadLeft),
+            Button::DPadRight => Some(GamepadButton::DPadRight),
+            Button::Start => Some(GamepadButton::Start),
+            Button::South => Some(GamepadButton::South),
+            Button::West => Some(GamepadButton::West),
+            _ => None,
+        };
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            let event = match event {
+                EventType::ButtonPressed(button, _) => to_button(button).map(GamepadEvent::Pressed),
+                EventType::ButtonReleased(button, _) => {
+                    to_button(button).map(GamepadEvent::Released)
+                }
+                EventType::AxisChanged(Axis::LeftStickX, value, _) => {
+                    Some(GamepadEvent::AxisChanged(GamepadAxis::LeftStickX, value))
+                }
+                EventType::AxisChanged(Axis::LeftStickY, value, _) => {
+                    Some(GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, value))
+                }
+                _ => None,
+            };
+            events.extend(event);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(gamepad: &mut Gamepad, events: &[GamepadEvent]) {
+        gamepad.begin_frame();
+        for event in events {
+            gamepad.handle(*event);
+        }
+    }
+
+    #[test]
+    fn test_dpad_and_stick_moves() {
+        let mut gamepad = Gamepad::default();
+        frame(
+            &mut gamepad,
+            &[GamepadEvent::Pressed(GamepadButton::DPadLeft)],
+        );
+        assert_eq!(gamepad.held_move(), Some(Action::MoveLeft));
+        assert_eq!(gamepad.pressed_move(), Some(Action::MoveLeft));
+        frame(&mut gamepad, &[]);
+        assert_eq!(gamepad.held_move(), Some(Action::MoveLeft));
+        assert_eq!(gamepad.pressed_move(), None);
+
+        let stick_down = GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, -0.9);
+        let released = GamepadEvent::Released(GamepadButton::DPadLeft);
+        frame(&mut gamepad, &[released, stick_down]);
+        assert_eq!(gamepad.held_move(), Some(Action::MoveDown));
+        assert_eq!(gamepad.pressed_move(), Some(Action::MoveDown));
+        frame(&mut gamepad, &[]);
+        assert_eq!(gamepad.pressed_move(), None);
+
+        frame(
+            &mut gamepad,
+            &[GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, 0.2)],
+        );
+        assert_eq!(gamepad.held_move(), None);
+    }
+
+    #[test]
+    fn test_focus_navigation() {
+        let mut gamepad = Gamepad::default();
+        let mut focus = Focus::default();
+        let down = GamepadEvent::Pressed(GamepadButton::DPadDown);
+        let confirm = GamepadEvent::Pressed(GamepadButton::South);
+
+        frame(&mut gamepad, &[down]);
+        assert_eq!(focus.update("pause", 3, &gamepad), None);
+        assert_eq!(focus.index(), 1);
+        frame(
+            &mut gamepad,
+            &[GamepadEvent::Pressed(GamepadButton::DPadUp)],
+        );
+        focus.update("pause", 3, &gamepad);
+        frame(
+            &mut gamepad,
+            &[GamepadEvent::Pressed(GamepadButton::DPadUp)],
+        );
+        focus.update("pause", 3, &gamepad);
+        assert_eq!(focus.index(), 2);
+        frame(&mut gamepad, &[confirm]);
+        assert_eq!(focus.update("pause", 3, &gamepad), Some(2));
+
+        frame(&mut gamepad, &[confirm]);
+        assert_eq!(focus.update("respawn", 1, &gamepad), Some(0));
+    }
+}
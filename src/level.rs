@@ -0,0 +1,87 @@
+use crate::map::TileDensity;
+
+/// Difficulty parameters of a single level.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LevelConfig {
+    pub density: TileDensity,
+    pub door_count: i32,
+    /// Monsters move once every this many ticks, unless the game is turn based.
+    pub monster_period_ticks: u64,
+    pub starting_health: f32,
+}
+
+/// The levels the player goes through, from easiest to hardest.
+pub const CAMPAIGN: [LevelConfig; 5] = [
+    LevelConfig {
+        density: TileDensity {
+            wall: 49,
+            coin: 2,
+            monster: 2,
+            health: 1,
+        },
+        door_count: 4,
+        monster_period_ticks: 60,
+        starting_health: 5.0,
+    },
+    LevelConfig {
+        density: TileDensity {
+            wall: 47,
+            coin: 2,
+            monster: 3,
+            health: 1,
+        },
+        door_count: 4,
+        monster_period_ticks: 50,
+        starting_health: 5.0,
+    },
+    LevelConfig {
+        density: TileDensity {
+            wall: 45,
+            coin: 2,
+            monster: 4,
+            health: 1,
+        },
+        door_count: 5,
+        monster_period_ticks: 45,
+        starting_health: 4.0,
+    },
+    LevelConfig {
+        density: TileDensity {
+            wall: 43,
+            coin: 3,
+            monster: 5,
+            health: 1,
+        },
+        door_count: 6,
+        monster_period_ticks: 40,
+        starting_health: 4.0,
+    },
+    LevelConfig {
+        density: TileDensity {
+            wall: 40,
+            coin: 3,
+            monster: 6,
+            health: 1,
+        },
+        door_count: 7,
+        monster_period_ticks: 30,
+        starting_health: 3.0,
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::MAX_HEALTH;
+
+    #[test]
+    fn test_campaign_is_valid() {
+        for config in CAMPAIGN {
+            let density = config.density;
+            assert!(density.wall + density.coin + density.monster + density.health <= 100);
+            assert!(config.door_count > 0);
+            assert!(config.monster_period_ticks > 0);
+            assert!(0.0 < config.starting_health && config.starting_health <= MAX_HEALTH);
+        }
+    }
+}
@@ -1,188 +1,577 @@
+mod animation;
+mod args;
+mod bindings;
+mod camera;
+mod chunk;
+mod clock;
+mod game;
+mod gamepad;
+mod level;
 mod map;
+mod monster;
+mod repeat;
+mod replay;
+mod rng;
+mod save;
+#[cfg(test)]
+mod test_util;
+mod theme;
+mod tileset;
+mod touch;
+mod versioned;
+mod walk;
 
-use crate::map::{Coord, Coord2, Map};
-use crate::map::{CoordDiff, CoordDiff2, Tile, DOWN, LEFT, RIGHT, UP};
+use crate::animation::Animator;
+use crate::args::{Args, USAGE};
+use crate::bindings::{Bindings, Command, Preset, DEFAULT_BINDINGS_PATH};
+use crate::camera::{Camera, Viewport};
+use crate::clock::Clock;
+use crate::game::{Action, GameEvent, GameState, ATTACK_COOLDOWN_TICKS, MAX_HEALTH};
+use crate::gamepad::{Focus, Gamepad, GamepadButton, GamepadSource};
+use crate::map::{CoordDiff2, Map, Tile};
+use crate::monster::MonsterKind;
+use crate::repeat::Repeater;
+use crate::replay::{Playback, Replay, Session};
+use crate::save::DEFAULT_SAVE_PATH;
+use crate::theme::{Theme, DEFAULT_THEME_PATH};
+use crate::tileset::{wall_neighbours, Sprite, TilesetMapping, DEFAULT_TILESET_PATH};
+use crate::touch::Swipes;
+use crate::walk::Walk;
 use juquad::draw::{draw_rect, draw_rect_lines};
 use juquad::input::input_macroquad::InputMacroquad;
 use juquad::widgets::anchor::Anchor;
 use juquad::widgets::button::{Button, Interaction, InteractionStyle, Style};
 use juquad::widgets::text::TextRect;
 use macroquad::prelude::*;
-use macroquad::rand::rand;
 
 const DEFAULT_WINDOW_WIDTH: i32 = 800;
 const DEFAULT_WINDOW_HEIGHT: i32 = 600;
 const DEFAULT_WINDOW_TITLE: &str = "Dream Maze";
+const DEFAULT_SEED: u64 = 42000;
 
 type Pixels = f32;
 type Pixels2 = Vec2;
+pub type AnyError = Box<dyn std::error::Error>;
 
-// https://supercolorpalette.com/?scp=G0-hsl-E4A84E-B2DF49-45D945-41D2A7-3E93CC-483BC4-9F3DB8-AB3F75
-const COLOR_BACKGROUND: Color = color_from_hex(0x3E93CCFF);
-const COLOR_WALL: Color = color_from_hex(0xE4A84EFF);
-const COLOR_DOOR: Color = color_from_hex(0x7C351DFF);
-const COLOR_PLAYER: Color = color_from_hex(0x45D945FF);
-const COLOR_MONSTER: Color = color_from_hex(0x9F3DB8FF);
-
-const COLOR_UI_BG: Color = color_from_hex(0xf9e1ffFF);
-const COLOR_UI_LIGHTER: Color = color_from_hex(0xCB9FD5FF);
-const COLOR_UI: Color = color_from_hex(0x9C4CAEFF);
-const COLOR_UI_DARKER: Color = color_from_hex(0x4F2759FF);
+/// How long the ring around the player lasts after an attack.
+const ATTACK_EFFECT_TICKS: i32 = 10;
 const FONT_SIZE: f32 = 16.0;
-const STYLE: Style = Style {
-    text_color: InteractionStyle {
-        at_rest: COLOR_UI_BG,
-        hovered: COLOR_UI_DARKER,
-        pressed: COLOR_UI_LIGHTER,
-    },
-    bg_color: InteractionStyle {
-        at_rest: COLOR_UI,
-        hovered: COLOR_UI_LIGHTER,
-        pressed: COLOR_UI_DARKER,
-    },
-    border_color: InteractionStyle {
-        at_rest: COLOR_UI,
-        hovered: COLOR_UI_DARKER,
-        pressed: DARKGRAY,
-    },
-};
-
-const MAX_HEALTH: f32 = 5.0;
-const REQUIRED_DOORS: i32 = 4;
-
-pub struct GameState {
-    player_health: f32,
-    map: Map,
-    doors_parts_collected: i32,
-}
-impl GameState {
-    pub fn new(screen_tiles: Coord2, player: Coord2) -> Self {
-        Self {
-            player_health: MAX_HEALTH,
-            map: Map::new(screen_tiles, player),
-            doors_parts_collected: 0,
+
+fn main() {
+    let args = match Args::parse(std::env::args().skip(1), DEFAULT_SEED) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
         }
+    };
+    let replay = match &args.replay {
+        Some(path) => match Replay::load(path) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                eprintln!("could not load replay {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    match replay {
+        Some(replay) if args.headless => print_outcome(&replay.run_headless(), replay.steps),
+        _ => macroquad::Window::from_config(window_conf(), game_loop(args, replay)),
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    macroquad::rand::srand(42000);
-    let tile_size = Pixels2::new(32.0, 32.0);
-    let screen_tiles = pixel_to_tile(screen_width(), screen_height(), tile_size);
-    println!("map size: {:?}", screen_tiles);
-    let player = screen_tiles / 2;
-    let mut game_state = GameState::new(screen_tiles, player);
-    let mut accumulated_pos = CoordDiff2::new(0, 0);
-    let mut next_door = calculate_rand_accumulated_pos(accumulated_pos, player, screen_tiles);
-    let mut paused = false;
-    let mut frame = 0;
+fn print_outcome(game_state: &GameState, steps: u64) {
+    println!(
+        "after {} steps: health {}, door parts {}, position {:?}, {}",
+        steps,
+        game_state.player_health,
+        game_state.doors_parts_collected,
+        game_state.accumulated_pos,
+        if game_state.has_won() {
+            "won"
+        } else if game_state.is_dead() {
+            "dead"
+        } else {
+            "still playing"
+        }
+    );
+}
+
+async fn game_loop(args: Args, replay: Option<Replay>) {
+    let mut camera = Camera::new(args.margin);
+    let tileset = load_tileset(args.tileset.as_deref()).await;
+    let mut bindings = load_bindings();
+    let mut pad = GamepadInput::open();
+    let themes = load_themes(args.theme.as_deref());
+    let mut theme_index = 0;
+    let (mut session, mut playback) = match replay {
+        Some(replay) => (Session::from_replay(&replay), Some(replay.playback())),
+        None => {
+            let continued = if std::path::Path::new(DEFAULT_SAVE_PATH).exists() {
+                match draw_start_ui(&bindings, &mut pad, &themes[0]).await {
+                    Some(StartChoice::Continue) => load_game(),
+                    Some(StartChoice::NewGame) => None,
+                    None => return,
+                }
+            } else {
+                None
+            };
+            let mut session = continued.unwrap_or_else(|| {
+                let screen_tiles = camera.map_tiles(window_size());
+                let player = screen_tiles / 2;
+                let (seed, mode, pacing) = (args.seed, args.mode, args.pacing);
+                Session::new(screen_tiles, player, seed, mode, pacing, camera.margin)
+            });
+            if let Some(path) = &args.world {
+                // restarts and new levels stay in this world, which is saved there when quitting
+                session.game_state.persistent_world = true;
+                if std::path::Path::new(path).exists() {
+                    if let Err(e) = session.game_state.load_world(path) {
+                        eprintln!("could not load world {}: {}", path, e);
+                    }
+                }
+                // the replay starts from the loaded world, not from the bare seed
+                session = Session::from_saved_game(session.game_state);
+            }
+            (session, None)
+        }
+    };
+    println!("map size: {:?}", session.game_state.map.size());
+    let mut controls: Option<ControlsScreen> = None;
+    let mut repeater = Repeater::default();
+    let mut held = None;
+    let mut walk: Option<Walk> = None;
+    let mut swipes = Swipes::default();
+    let mut show_dpad = false;
+    let mut touched = false;
+    let mut clock = Clock::new(args.tick);
+    let mut animator = Animator::new(args.animation);
+    let mut pending = None;
     loop {
-        clear_background(LIGHTGRAY);
-        if is_key_down(KeyCode::Escape) {
+        let theme = &themes[theme_index];
+        clear_background(theme.outside);
+        pad.poll();
+        let rebinding = controls.as_ref().is_some_and(|c| c.waiting.is_some());
+        // pressed rather than down, or the key that cancelled or bound a rebinding would quit
+        if !rebinding && is_command_pressed(&bindings, Command::Quit) {
+            if let Some(path) = &args.record {
+                match session.replay.save(path) {
+                    Ok(()) => println!("replay saved to {}", path),
+                    Err(e) => eprintln!("could not save replay to {}: {}", path, e),
+                }
+            }
+            if let Some(path) = &args.world {
+                match session.game_state.map.save_world(path) {
+                    Ok(()) => println!("world saved to {}", path),
+                    Err(e) => eprintln!("could not save world to {}: {}", path, e),
+                }
+            }
             break;
         }
-        if is_key_pressed(KeyCode::Space) {
-            paused = !paused;
+        let ticks = clock.update(get_frame_time());
+        animator.update(get_frame_time());
+        let replaying = playback.as_ref().is_some_and(|p| !p.is_finished());
+        if replaying {
+            for _ in 0..ticks {
+                if let Some(action) = playback.as_mut().and_then(Playback::next_action) {
+                    for event in session.step(action) {
+                        animator.handle(&event);
+                    }
+                }
+            }
+        } else {
+            let (_, wheel) = mouse_wheel();
+            if wheel != 0.0 && controls.is_none() && !session.game_state.paused {
+                camera.zoom_by(wheel.signum());
+            }
+            let map_tiles = camera.map_tiles(window_size());
+            if map_tiles != session.game_state.map.size() {
+                session.step(Action::Resize(map_tiles));
+            }
         }
-        if paused {
-            if draw_paused_ui().is_clicked() {
-                paused = false;
+        if !replaying
+            && controls.is_none()
+            && (is_command_pressed(&bindings, Command::Pause)
+                || pad.gamepad.is_pressed(GamepadButton::Start))
+        {
+            session.step(Action::Pause);
+        }
+        if let Some(screen) = &mut controls {
+            if screen.update(&mut bindings, &mut pad, theme) {
+                controls = None;
             }
             next_frame().await;
             continue;
         }
-        if is_key_pressed(KeyCode::Down) {
-            if game_state.map.move_down() {
-                accumulated_pos += DOWN;
+        if session.game_state.paused {
+            match draw_paused_ui(&mut pad, show_dpad, theme).filter(|_| !replaying) {
+                Some(PauseChoice::Resume) => {
+                    session.step(Action::Pause);
+                }
+                Some(PauseChoice::Save) => match save::save(&session.game_state, DEFAULT_SAVE_PATH)
+                {
+                    Ok(()) => println!("game saved to {}", DEFAULT_SAVE_PATH),
+                    Err(e) => eprintln!("could not save game to {}: {}", DEFAULT_SAVE_PATH, e),
+                },
+                Some(PauseChoice::Load) => {
+                    if let Some(loaded) = load_game() {
+                        session = loaded;
+                        animator.reset();
+                        playback = None;
+                    }
+                }
+                Some(PauseChoice::Controls) => controls = Some(ControlsScreen { waiting: None }),
+                Some(PauseChoice::ToggleDPad) => show_dpad = !show_dpad,
+                Some(PauseChoice::NextTheme) => theme_index = (theme_index + 1) % themes.len(),
+                None => {}
             }
+            next_frame().await;
+            continue;
         }
-        if is_key_pressed(KeyCode::Up) {
-            if game_state.map.move_up() {
-                accumulated_pos += UP;
+        let dpad = show_dpad.then(DPad::new);
+        if !replaying {
+            let on_dpad = |pos| dpad.as_ref().is_some_and(|dpad| dpad.contains(pos));
+            let mut swiped = None;
+            for touch in touches() {
+                if !touched {
+                    touched = true;
+                    show_dpad = true;
+                }
+                match touch.phase {
+                    TouchPhase::Started if !on_dpad(touch.position) => {
+                        swipes.start(touch.id, touch.position)
+                    }
+                    TouchPhase::Ended => {
+                        let min_distance = screen_width() * 0.08;
+                        swiped = swiped.or(swipes.end(touch.id, touch.position, min_distance));
+                    }
+                    TouchPhase::Cancelled => swipes.cancel(touch.id),
+                    _ => {}
+                }
             }
-        }
-        if is_key_pressed(KeyCode::Left) {
-            if game_state.map.move_left() {
-                accumulated_pos += LEFT;
+            let click = Vec2::from(mouse_position());
+            if is_mouse_button_released(MouseButton::Left)
+                && !session.game_state.is_over()
+                && !on_dpad(click)
+                && swiped.is_none()
+            {
+                let map = &session.game_state.map;
+                let viewport = camera.viewport(window_size(), map.size(), map.player);
+                if let Some(clicked_tile) = viewport.to_tile(click) {
+                    walk = Walk::new(map, clicked_tile);
+                }
             }
-        }
-        if is_key_pressed(KeyCode::Right) {
-            if game_state.map.move_right() {
-                accumulated_pos += RIGHT;
+            held = read_held_move(&bindings, held)
+                .or_else(|| pad.gamepad.held_move())
+                .or_else(|| dpad.as_ref().and_then(DPad::held_move));
+            if held.is_some() || swiped.is_some() {
+                walk = None;
             }
-        }
-        if is_mouse_button_released(MouseButton::Left) {
-            let click = Vec2::from(mouse_position());
-            let clicked_tile = pixel_to_tile(click.x, click.y, tile_size);
-            let tile = game_state.map.get(clicked_tile);
-            println!("tile at {:?} is {:?}", clicked_tile, tile);
-        }
-
-        let player_tile = game_state.map.get(game_state.map.player);
-        if (frame + 1) % 60 == 0 {
-            game_state.map.advance();
-            if player_tile == Tile::Monster {
-                game_state.player_health = 0.0_f32.max(game_state.player_health - 1.0);
+            let clicked = dpad.as_ref().and_then(DPad::clicked_action);
+            let attack = is_command_pressed(&bindings, Command::Attack)
+                || pad.gamepad.is_pressed(GamepadButton::South)
+                || clicked == Some(Action::Attack);
+            let wait = is_command_pressed(&bindings, Command::Wait)
+                || pad.gamepad.is_pressed(GamepadButton::West)
+                || clicked == Some(Action::Wait);
+            let delta = get_frame_time();
+            let action = match (swiped, &mut walk) {
+                _ if attack => Action::Attack,
+                _ if wait => Action::Wait,
+                (Some(action), _) => action,
+                (None, Some(walk)) => {
+                    walk.update(&session.game_state.map, bindings.repeat.interval, delta)
+                }
+                (None, None) => repeater.update(bindings.repeat, held, delta),
+            };
+            // an action from a frame without ticks waits for the next tick
+            if action != Action::Idle {
+                pending = Some(action);
             }
-        }
-        if accumulated_pos == next_door {
-            game_state.doors_parts_collected += 1;
-            if game_state.doors_parts_collected < REQUIRED_DOORS {
-                next_door = calculate_rand_accumulated_pos(accumulated_pos, player, screen_tiles);
+            for _ in 0..ticks {
+                let events = session.step(pending.take().unwrap_or(Action::Idle));
+                for event in &events {
+                    animator.handle(event);
+                }
+                if events.iter().any(|e| matches!(e, GameEvent::Blocked(_))) {
+                    repeater.stop();
+                }
+            }
+            if walk.as_ref().is_some_and(Walk::is_finished) {
+                walk = None;
             }
         }
+        let game_state = &session.game_state;
+        let map = &game_state.map;
+        let viewport = camera.viewport(window_size(), map.size(), map.player);
 
-        let end_of_map = tile_to_pixel(screen_tiles.x, screen_tiles.y, tile_size);
-        draw_rectangle(0.0, 0.0, end_of_map.x, end_of_map.y, COLOR_BACKGROUND);
-        draw_map(tile_size, screen_tiles, &game_state.map);
-        draw_player(tile_size, player);
-        draw_door(tile_size, player, screen_tiles, accumulated_pos, next_door);
+        let end_of_map = viewport.end();
+        draw_rectangle(0.0, 0.0, end_of_map.x, end_of_map.y, theme.background);
+        let look = Look {
+            theme,
+            tileset: tileset.as_ref(),
+        };
+        draw_map(&viewport, map, &animator, look);
+        draw_player(&viewport, map.player, look);
+        draw_attack(&viewport, game_state, theme);
+        draw_door(&viewport, game_state, &animator, look);
 
-        draw_health_ui(game_state.player_health);
-        draw_doors_ui(&mut game_state.doors_parts_collected);
-        if game_state.player_health <= 0.0 {
-            if draw_respawn_ui().is_clicked() {
-                game_state = GameState::new(screen_tiles, player);
-            }
+        draw_health_ui(game_state, theme);
+        draw_doors_ui(
+            game_state.doors_parts_collected,
+            game_state.config().door_count,
+            theme,
+        );
+        if let Some(dpad) = &dpad {
+            dpad.render(theme);
         }
-        if game_state.doors_parts_collected >= 4 {
-            if draw_game_won().is_clicked() {
-                game_state = GameState::new(screen_tiles, player);
-            }
+        let mut action = None;
+        if game_state.is_dead() && draw_respawn_ui(&mut pad, theme) {
+            action = Some(Action::Restart);
+        }
+        if game_state.has_won() && draw_game_won(game_state.is_last_level(), &mut pad, theme) {
+            action = Some(if game_state.is_last_level() {
+                Action::Restart
+            } else {
+                Action::NextLevel
+            });
         }
 
-        if is_key_down(KeyCode::F3) {
+        if is_command_down(&bindings, Command::Debug) {
             draw_text(
-                &format!(" FPS: {}", get_fps()),
+                &format!(
+                    " FPS: {}, seed: {}, mode: {:?}",
+                    get_fps(),
+                    game_state.seed,
+                    game_state.mode
+                ),
                 0.0,
                 screen_height() - FONT_SIZE * 0.5,
                 FONT_SIZE,
-                BLACK,
+                theme.text,
             );
         }
-        frame = (frame + 1) % 10000;
+        if let Some(action) = action.filter(|_| !replaying) {
+            for event in session.step(action) {
+                animator.handle(&event);
+            }
+        }
         next_frame().await
     }
 }
 
-fn draw_door(
-    tile_size: Pixels2,
-    player: Coord2,
-    screen_tiles: Coord2,
-    accumulated_pos: CoordDiff2,
-    next_door: CoordDiff2,
-) {
-    let door_pos = next_door - accumulated_pos + to_signed(player);
-    if door_pos.x >= 0
-        && door_pos.x < screen_tiles.x as CoordDiff
-        && door_pos.y >= 0
-        && door_pos.y < screen_tiles.y as CoordDiff
-    {
-        let mut pixel = tile_to_pixel(door_pos.x as Coord, door_pos.y as Coord, tile_size);
+/// The tileset given in the arguments, or the default one if that file exists. Without a tileset
+/// everything is drawn with plain shapes.
+async fn load_tileset(path: Option<&str>) -> Option<Tileset> {
+    let path = match path {
+        Some(path) => path,
+        None if std::path::Path::new(DEFAULT_TILESET_PATH).exists() => DEFAULT_TILESET_PATH,
+        None => return None,
+    };
+    match Tileset::load(path).await {
+        Ok(tileset) => {
+            println!("tileset loaded from {}", path);
+            Some(tileset)
+        }
+        Err(e) => {
+            eprintln!("could not load tileset {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// The built-in themes, after the one given in the arguments or the default theme file if any.
+fn load_themes(path: Option<&str>) -> Vec<Theme> {
+    let mut themes = Theme::built_in();
+    let path = match path {
+        Some(path) => path,
+        None if std::path::Path::new(DEFAULT_THEME_PATH).exists() => DEFAULT_THEME_PATH,
+        None => return themes,
+    };
+    match Theme::load(path) {
+        Ok(theme) => {
+            println!("theme loaded from {}", path);
+            themes.insert(0, theme);
+        }
+        Err(e) => eprintln!("could not load theme {}: {}", path, e),
+    }
+    themes
+}
+
+/// The loaded game starts unpaused, although it was saved from the pause menu.
+fn load_game() -> Option<Session> {
+    match save::load(DEFAULT_SAVE_PATH) {
+        Ok(mut game_state) => {
+            println!("game loaded from {}", DEFAULT_SAVE_PATH);
+            game_state.paused = false;
+            Some(Session::from_saved_game(game_state))
+        }
+        Err(e) => {
+            eprintln!("could not load game from {}: {}", DEFAULT_SAVE_PATH, e);
+            None
+        }
+    }
+}
+
+fn load_bindings() -> Bindings {
+    if !std::path::Path::new(DEFAULT_BINDINGS_PATH).exists() {
+        return Bindings::default();
+    }
+    match Bindings::load(DEFAULT_BINDINGS_PATH) {
+        Ok(bindings) => {
+            for command in Command::ALL {
+                for key in bindings.keys(command) {
+                    if key_code(key).is_none() {
+                        eprintln!("unknown key '{}' for {}", key, command.name());
+                    }
+                }
+            }
+            bindings
+        }
+        Err(e) => {
+            eprintln!(
+                "could not load key bindings from {}: {}",
+                DEFAULT_BINDINGS_PATH, e
+            );
+            Bindings::default()
+        }
+    }
+}
+
+/// The keys that can be used in the bindings, which are named after their `Debug` output.
+const BINDABLE_KEYS: [KeyCode; 70] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Escape,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::LeftShift,
+    KeyCode::LeftControl,
+    KeyCode::RightShift,
+];
+
+fn key_code(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .into_iter()
+        .find(|key| format!("{:?}", key) == name)
+}
+
+fn key_codes(bindings: &Bindings, command: Command) -> impl Iterator<Item = KeyCode> + '_ {
+    bindings
+        .keys(command)
+        .iter()
+        .filter_map(|name| key_code(name))
+}
+
+fn is_command_pressed(bindings: &Bindings, command: Command) -> bool {
+    key_codes(bindings, command).any(is_key_pressed)
+}
+
+fn is_command_down(bindings: &Bindings, command: Command) -> bool {
+    key_codes(bindings, command).any(is_key_down)
+}
+
+const MOVE_COMMANDS: [Command; 4] = [
+    Command::MoveDown,
+    Command::MoveUp,
+    Command::MoveLeft,
+    Command::MoveRight,
+];
+
+/// The move of the key being held. A newly pressed key wins over the ones already down.
+fn read_held_move(bindings: &Bindings, previous: Option<Action>) -> Option<Action> {
+    let find = |is_active: fn(&Bindings, Command) -> bool| {
+        MOVE_COMMANDS
+            .into_iter()
+            .find(|command| is_active(bindings, *command))
+            .and_then(Command::action)
+    };
+    find(is_command_pressed)
+        .or(previous.filter(|action| {
+            MOVE_COMMANDS
+                .into_iter()
+                .any(|c| c.action() == Some(*action) && is_command_down(bindings, c))
+        }))
+        .or_else(|| find(is_command_down))
+}
+
+fn draw_door(viewport: &Viewport, game_state: &GameState, animator: &Animator, look: Look) {
+    if game_state.doors_parts_collected >= game_state.config().door_count {
+        return;
+    }
+    let door_pos = game_state.to_screen(game_state.next_door);
+    if let Some(door_pos) = door_pos.filter(|pos| viewport.contains(*pos)) {
+        let tile_size = viewport.tile_size;
+        let mut pixel = viewport.pixel_at(door_pos.as_vec2() + animator.scroll());
+        if draw_sprite(look.tileset, Sprite::Door, pixel, tile_size) {
+            return;
+        }
         pixel += tile_size * 0.25;
         let door_size = tile_size * 0.5;
-        draw_rectangle(pixel.x, pixel.y, door_size.x, door_size.y, COLOR_DOOR);
+        draw_rectangle(pixel.x, pixel.y, door_size.x, door_size.y, look.theme.door);
     }
 }
 
@@ -196,60 +585,169 @@ fn window_conf() -> Conf {
     }
 }
 
-fn calculate_rand_accumulated_pos(
-    accumulated_pos: CoordDiff2,
-    player: Coord2,
-    screen_tiles: Coord2,
-) -> CoordDiff2 {
-    let area = screen_tiles.x * screen_tiles.y;
-    let i = (rand() % area) as i32;
-    scalar_to_around_accumulated_pos(accumulated_pos, player, screen_tiles, i)
-}
-
-fn scalar_to_around_accumulated_pos(
-    accumulated_pos: CoordDiff2,
-    player: Coord2,
-    screen_tiles: Coord2,
-    i: i32,
-) -> CoordDiff2 {
-    let door_pos_unsigned = CoordDiff2::new(i % screen_tiles.x as i32, i / screen_tiles.x as i32);
-    let player = to_signed(player);
-    let door_pos = door_pos_unsigned - player + accumulated_pos;
-    door_pos
-}
-
-fn to_signed(pos: Coord2) -> CoordDiff2 {
-    CoordDiff2::new(pos.x as CoordDiff, pos.y as CoordDiff)
+/// The first and last tiles to draw, relative to the top left one of the viewport: the visible
+/// ones, plus the row or column that slides into view while scrolling. Nothing else is drawn, so
+/// the map doesn't spill over the `outside` strip of the theme.
+fn drawn_tiles(viewport: &Viewport, scroll: Vec2) -> (CoordDiff2, CoordDiff2) {
+    let (zero, one) = (CoordDiff2::ZERO, CoordDiff2::ONE);
+    let first = -CoordDiff2::select(scroll.cmpgt(Vec2::ZERO), one, zero);
+    let last = viewport.tiles.as_ivec2() - CoordDiff2::select(scroll.cmplt(Vec2::ZERO), zero, one);
+    (first, last)
 }
 
-fn draw_map(tile_size: Vec2, screen_tiles: Coord2, map: &Map) {
-    for i_x in 0..screen_tiles.x {
-        for i_y in 0..screen_tiles.y {
-            let tile = map.get(Coord2::new(i_x, i_y));
-            let pixel = tile_to_pixel(i_x, i_y, tile_size);
+/// The world is drawn where the animations are, which may be between tiles.
+fn draw_map(viewport: &Viewport, map: &Map, animator: &Animator, look: Look) {
+    let Look { theme, tileset } = look;
+    let tile_size = viewport.tile_size;
+    let scroll = animator.scroll();
+    let (first, last) = drawn_tiles(viewport, scroll);
+    for i_x in first.x..=last.x {
+        for i_y in first.y..=last.y {
+            let relative = CoordDiff2::new(i_x, i_y);
+            let pos = map.add_coord(viewport.first, relative);
+            let tile = map.get(pos);
+            let pixel = (relative.as_vec2() + scroll) * tile_size;
+            if let Some(tileset) = tileset {
+                if tile == Tile::Wall {
+                    let neighbours = wall_neighbours(map, pos);
+                    if let Some(source) = tileset.mapping.wall_source(neighbours) {
+                        tileset.draw(source, pixel, tile_size);
+                        continue;
+                    }
+                } else {
+                    draw_sprite(Some(tileset), Sprite::Floor, pixel, tile_size);
+                    let sprite = Sprite::of_tile(tile);
+                    if sprite
+                        .is_some_and(|sprite| draw_sprite(Some(tileset), sprite, pixel, tile_size))
+                    {
+                        continue;
+                    }
+                }
+            }
             match tile {
                 Tile::Wall => {
-                    draw_rectangle(pixel.x, pixel.y, tile_size.x, tile_size.y, COLOR_WALL)
+                    draw_rectangle(pixel.x, pixel.y, tile_size.x, tile_size.y, theme.wall)
+                }
+                Tile::Exit => {
+                    let exit_pos = pixel + tile_size * Vec2::new(0.2, 0.1);
+                    let exit_size = tile_size * Vec2::new(0.6, 0.9);
+                    draw_rectangle(exit_pos.x, exit_pos.y, exit_size.x, exit_size.y, theme.door);
+                    let knob = pixel + tile_size * Vec2::new(0.65, 0.55);
+                    draw_circle(knob.x, knob.y, tile_size.x * 0.05, theme.coin);
                 }
-                Tile::Monster => {
-                    let top = pixel + Vec2::new(tile_size.x * 0.5, tile_size.y * 0.2);
-                    let left = pixel + Vec2::new(tile_size.x * 0.2, tile_size.y * 0.8);
-                    let right = pixel + Vec2::new(tile_size.x * 0.8, tile_size.y * 0.8);
-                    draw_triangle(top, left, right, COLOR_MONSTER);
+                Tile::Coin => {
+                    let center = pixel + tile_size * 0.5;
+                    draw_circle(center.x, center.y, tile_size.x * 0.15, theme.coin);
                 }
-                Tile::Floor => {}
+                Tile::Health => {
+                    let long = tile_size * Vec2::new(0.6, 0.2);
+                    let center = pixel + tile_size * 0.5;
+                    let horizontal = center - long * 0.5;
+                    let vertical = center - Vec2::new(long.y, long.x) * 0.5;
+                    draw_rectangle(horizontal.x, horizontal.y, long.x, long.y, theme.health);
+                    draw_rectangle(vertical.x, vertical.y, long.y, long.x, theme.health);
+                }
+                Tile::Floor | Tile::Monster(_) => {}
             };
         }
     }
+    for monster in map.monsters() {
+        let pos = map.world_to_view(monster.pos);
+        let sliding = animator.monster_position(monster.pos) - monster.pos.as_vec2();
+        let drawn_at = pos.as_vec2() + sliding + scroll;
+        let relative = drawn_at - viewport.first.as_vec2();
+        if relative.cmplt(first.as_vec2()).any() || relative.cmpgt(last.as_vec2()).any() {
+            continue;
+        }
+        let pixel = viewport.pixel_at(drawn_at);
+        if draw_sprite(tileset, Sprite::of_monster(monster.kind), pixel, tile_size) {
+            continue;
+        }
+        let top = pixel + Vec2::new(tile_size.x * 0.5, tile_size.y * 0.2);
+        let left = pixel + Vec2::new(tile_size.x * 0.2, tile_size.y * 0.8);
+        let right = pixel + Vec2::new(tile_size.x * 0.8, tile_size.y * 0.8);
+        let color = match monster.kind {
+            MonsterKind::Stalker => theme.stalker,
+            MonsterKind::Wanderer => theme.wanderer,
+            MonsterKind::WallHugger => theme.wall_hugger,
+            MonsterKind::Chaser => theme.chaser,
+        };
+        draw_triangle(top, left, right, color);
+    }
 }
 
-fn draw_player(tile_size: Vec2, player: UVec2) {
-    let mut pixel = tile_to_pixel(player.x, player.y, tile_size);
+fn draw_player(viewport: &Viewport, player: UVec2, look: Look) {
+    let tile_size = viewport.tile_size;
+    let mut pixel = viewport.to_pixel(player);
+    if draw_sprite(look.tileset, Sprite::Player, pixel, tile_size) {
+        return;
+    }
     pixel += tile_size * 0.5; // circle position is the center
-    draw_circle(pixel.x, pixel.y, 10.0, COLOR_PLAYER);
+    draw_circle(pixel.x, pixel.y, tile_size.x * 0.3, look.theme.player);
+}
+
+/// What the world is drawn with.
+#[derive(Copy, Clone)]
+struct Look<'a> {
+    theme: &'a Theme,
+    tileset: Option<&'a Tileset>,
 }
 
-fn draw_health_ui(player_health: f32) {
+/// The atlas image and where each sprite is in it.
+struct Tileset {
+    mapping: TilesetMapping,
+    texture: Texture2D,
+}
+
+impl Tileset {
+    async fn load(path: &str) -> Result<Self, AnyError> {
+        let mapping = TilesetMapping::load(path)?;
+        let texture = load_texture(&mapping.image_path(path)).await?;
+        texture.set_filter(FilterMode::Nearest);
+        Ok(Self { mapping, texture })
+    }
+
+    /// Draws the cell of the atlas at `source` over the tile whose top left corner is `pixel`.
+    fn draw(&self, source: Vec2, pixel: Vec2, tile_size: Vec2) {
+        let cell = self.mapping.tile_size as f32;
+        let params = DrawTextureParams {
+            dest_size: Some(tile_size),
+            source: Some(Rect::new(source.x, source.y, cell, cell)),
+            ..Default::default()
+        };
+        draw_texture_ex(self.texture, pixel.x, pixel.y, WHITE, params);
+    }
+}
+
+/// Returns false if there is no tileset or it lacks the sprite, so that the caller draws shapes.
+fn draw_sprite(tileset: Option<&Tileset>, sprite: Sprite, pixel: Vec2, tile_size: Vec2) -> bool {
+    match tileset.and_then(|tileset| Some((tileset, tileset.mapping.source(sprite)?))) {
+        Some((tileset, source)) => {
+            tileset.draw(source, pixel, tile_size);
+            true
+        }
+        None => false,
+    }
+}
+
+/// A ring that grows around the player right after an attack.
+fn draw_attack(viewport: &Viewport, game_state: &GameState, theme: &Theme) {
+    let since_attack = ATTACK_COOLDOWN_TICKS - game_state.attack_cooldown;
+    if game_state.attack_cooldown == 0 || since_attack >= ATTACK_EFFECT_TICKS {
+        return;
+    }
+    let progress = since_attack as f32 / ATTACK_EFFECT_TICKS as f32;
+    let tile_size = viewport.tile_size;
+    let center = viewport.to_pixel(game_state.map.player) + tile_size * 0.5;
+    let radius = tile_size.x * (0.5 + progress);
+    let mut color = theme.attack;
+    color.a = 1.0 - progress;
+    draw_circle_lines(center.x, center.y, radius, 3.0, color);
+}
+
+/// The health bar, with a thinner bar below it that fills up until the player can attack again.
+fn draw_health_ui(game_state: &GameState, theme: &Theme) {
+    let player_health = game_state.player_health;
     let health_unit: Pixels = 20.0;
     let thickness = 1.0;
     draw_rectangle(
@@ -257,68 +755,166 @@ fn draw_health_ui(player_health: f32) {
         10.0,
         MAX_HEALTH * health_unit + thickness * 2.0,
         health_unit + thickness * 2.0,
-        COLOR_UI_DARKER,
+        theme.ui_darker,
     );
     draw_rectangle(
         10.0 + thickness,
         10.0 + thickness,
         player_health * health_unit,
         health_unit,
-        COLOR_PLAYER,
+        theme.player,
+    );
+    let attack_ready = 1.0 - game_state.attack_cooldown as f32 / ATTACK_COOLDOWN_TICKS as f32;
+    draw_rectangle(
+        10.0 + thickness,
+        10.0 + health_unit + thickness * 2.0,
+        MAX_HEALTH * health_unit * attack_ready,
+        4.0,
+        theme.attack,
+    );
+
+    let text = format!(
+        "Level {}   Coins: {}",
+        game_state.level + 1,
+        game_state.score
+    );
+    let dimensions = measure_text(&text, None, FONT_SIZE as u16, 1.0);
+    let pad = 5.0;
+    let score_rect = Rect::new(
+        10.0 + MAX_HEALTH * health_unit + thickness * 2.0 + 10.0,
+        10.0,
+        dimensions.width + pad * 2.0,
+        health_unit + thickness * 2.0,
+    );
+    draw_rect(score_rect, theme.ui_lighter);
+    draw_rect_lines(score_rect, 2.0, theme.ui_darker);
+    draw_text(
+        &text,
+        score_rect.x + pad,
+        score_rect.y + (score_rect.h + dimensions.offset_y) * 0.5,
+        FONT_SIZE,
+        theme.ui_darker,
     );
 }
-fn draw_doors_ui(door_parts_collected_mut: &mut i32) {
-    let door_parts_collected = *door_parts_collected_mut;
-    let door_grid: Pixels = 20.0;
+fn draw_doors_ui(door_parts_collected: i32, door_parts_required: i32, theme: &Theme) {
     let door_part: Pixels = 15.0;
-    let pad = door_grid - door_part;
-    let ui_start_x = screen_width() - door_grid * 2.0 - 10.0;
+    let pad: Pixels = 5.0;
+    let columns = (door_parts_required as f32).sqrt().ceil() as i32;
+    let rows = (door_parts_required + columns - 1) / columns;
+    let width = door_part * columns as Pixels + pad * 2.0;
+    let height = door_part * rows as Pixels + pad * 2.0;
+    let ui_start_x = screen_width() - width - 10.0;
     let ui_start_y = 10.0;
-    let rect = Rect::new(ui_start_x, ui_start_y, door_grid * 2.0, door_grid * 2.0);
-    draw_rect(rect, COLOR_UI_LIGHTER);
-    draw_rect_lines(rect, 2.0, COLOR_UI_DARKER);
-    let draw_door = |x: Pixels, y: Pixels| {
+    let rect = Rect::new(ui_start_x, ui_start_y, width, height);
+    draw_rect(rect, theme.ui_lighter);
+    draw_rect_lines(rect, 2.0, theme.ui_darker);
+    for i in 0..door_parts_collected.min(door_parts_required) {
         draw_rectangle(
-            ui_start_x + x,
-            ui_start_y + y,
+            ui_start_x + pad + (i % columns) as Pixels * door_part,
+            ui_start_y + pad + (i / columns) as Pixels * door_part,
             door_part,
             door_part,
-            COLOR_DOOR,
+            theme.door,
         );
-    };
-    if door_parts_collected > 0 {
-        draw_door(pad, pad);
-    }
-    if door_parts_collected > 1 {
-        draw_door(door_grid, pad);
-    }
-    if door_parts_collected > 2 {
-        draw_door(pad, door_grid);
     }
-    if door_parts_collected > 3 {
-        draw_door(door_grid, door_grid);
-    }
-    // if is_mouse_button_released(MouseButton::Left)
-    //     && rect.contains(Vec2::from(InputMacroquad.mouse_position()))
-    // {
-    //     *door_parts_collected_mut = (*door_parts_collected_mut + 1) % 5;
-    // }
 }
-fn draw_paused_ui() -> Interaction {
-    let text_anchor = Anchor::top_center(screen_width() * 0.5, screen_height() * 0.45);
+enum PauseChoice {
+    Resume,
+    Save,
+    Load,
+    Controls,
+    ToggleDPad,
+    NextTheme,
+}
+
+fn draw_paused_ui(pad: &mut GamepadInput, show_dpad: bool, theme: &Theme) -> Option<PauseChoice> {
+    let text_anchor = Anchor::top_center(screen_width() * 0.5, screen_height() * 0.4);
     let text = TextRect::new("Paused", text_anchor, FONT_SIZE);
 
     let button_anchor = Anchor::center_below(text.rect, 0.0, 20.0);
     let mut resume = create_button("Resume (Press Space)", button_anchor);
     resume.interact();
+    let mut save = create_button("Save", Anchor::center_below(resume.rect(), 0.0, 10.0));
+    save.interact();
+    let mut load = create_button("Load", Anchor::center_below(save.rect(), 0.0, 10.0));
+    load.interact();
+    let controls_anchor = Anchor::center_below(load.rect(), 0.0, 10.0);
+    let mut controls = create_button("Controls", controls_anchor);
+    controls.interact();
+    let dpad_text = if show_dpad {
+        "Hide D-pad"
+    } else {
+        "Show D-pad"
+    };
+    let mut dpad = create_button(dpad_text, Anchor::center_below(controls.rect(), 0.0, 10.0));
+    dpad.interact();
+    let theme_text = format!("Theme: {}", theme.name);
+    let mut next_theme = create_button(&theme_text, Anchor::center_below(dpad.rect(), 0.0, 10.0));
+    next_theme.interact();
+
+    render_window(text.rect.combine_with(next_theme.rect()), theme);
+    text.render_text(theme.ui_darker);
+    let style = theme.style();
+    resume.render(&style);
+    save.render(&style);
+    load.render(&style);
+    controls.render(&style);
+    dpad.render(&style);
+    next_theme.render(&style);
+    let buttons = [&resume, &save, &load, &controls, &dpad, &next_theme];
+    match pad.choose("pause", &buttons, theme) {
+        Some(0) => Some(PauseChoice::Resume),
+        Some(1) => Some(PauseChoice::Save),
+        Some(2) => Some(PauseChoice::Load),
+        Some(3) => Some(PauseChoice::Controls),
+        Some(4) => Some(PauseChoice::ToggleDPad),
+        Some(_) => Some(PauseChoice::NextTheme),
+        None => None,
+    }
+}
 
-    render_window(text.rect.combine_with(resume.rect()));
-    text.render_text(COLOR_UI_DARKER);
-    resume.render(&STYLE);
-    resume.interaction()
+enum StartChoice {
+    NewGame,
+    Continue,
 }
 
-fn render_window(content: Rect) {
+/// Shown at startup when there is a saved game. Returns None if the player quits with Escape.
+async fn draw_start_ui(
+    bindings: &Bindings,
+    pad: &mut GamepadInput,
+    theme: &Theme,
+) -> Option<StartChoice> {
+    loop {
+        pad.poll();
+        if is_command_pressed(bindings, Command::Quit) {
+            return None;
+        }
+        clear_background(theme.background);
+        let text_anchor = Anchor::top_center(screen_width() * 0.5, screen_height() * 0.4);
+        let text = TextRect::new(DEFAULT_WINDOW_TITLE, text_anchor, FONT_SIZE);
+
+        let button_anchor = Anchor::center_below(text.rect, 0.0, 20.0);
+        let mut continue_button = create_button("Continue", button_anchor);
+        continue_button.interact();
+        let new_game_anchor = Anchor::center_below(continue_button.rect(), 0.0, 10.0);
+        let mut new_game = create_button("New game", new_game_anchor);
+        new_game.interact();
+
+        render_window(text.rect.combine_with(new_game.rect()), theme);
+        text.render_text(theme.ui_darker);
+        let style = theme.style();
+        continue_button.render(&style);
+        new_game.render(&style);
+        match pad.choose("start", &[&continue_button, &new_game], theme) {
+            Some(0) => return Some(StartChoice::Continue),
+            Some(_) => return Some(StartChoice::NewGame),
+            None => {}
+        }
+        next_frame().await
+    }
+}
+
+fn render_window(content: Rect, theme: &Theme) {
     let pad = 30.0;
     let window = Rect::new(
         content.x - pad,
@@ -326,24 +922,118 @@ fn render_window(content: Rect) {
         content.w + 2.0 * pad,
         content.h + 2.0 * pad,
     );
-    draw_rect(window, COLOR_UI_BG);
-    draw_rect_lines(window, 2.0, COLOR_UI_DARKER);
+    draw_rect(window, theme.ui_background);
+    draw_rect_lines(window, 2.0, theme.ui_darker);
 }
 
-fn draw_game_won() -> Interaction {
+/// Rebinding screen, reachable from the pause window.
+struct ControlsScreen {
+    /// The command that will be bound to the next key pressed.
+    waiting: Option<Command>,
+}
+
+impl ControlsScreen {
+    /// Returns true when the player leaves the screen. The bindings are saved then.
+    fn update(&mut self, bindings: &mut Bindings, pad: &mut GamepadInput, theme: &Theme) -> bool {
+        if let Some(command) = self.waiting {
+            if let Some(key) = get_last_key_pressed() {
+                let name = format!("{:?}", key);
+                if key == KeyCode::Escape && command != Command::Quit {
+                    self.waiting = None;
+                } else if key_code(&name).is_some() {
+                    bindings.bind(command, &name);
+                    self.waiting = None;
+                }
+                // keys that can't be bound are ignored, and the screen keeps waiting
+            }
+        }
+        let text_anchor = Anchor::top_center(screen_width() * 0.5, screen_height() * 0.1);
+        let message = match self.waiting {
+            Some(command) => format!("Press a key for {} (Escape to cancel)", command.name()),
+            None => "Click on a command to change its key".to_string(),
+        };
+        let text = TextRect::new(&message, text_anchor, FONT_SIZE);
+
+        let mut anchor = Anchor::center_below(text.rect, 0.0, 20.0);
+        let mut buttons = Vec::new();
+        for command in Command::ALL {
+            let keys = bindings.keys(command).join(", ");
+            let mut button = create_button(&format!("{}: {}", command.name(), keys), anchor);
+            button.interact();
+            anchor = Anchor::center_below(button.rect(), 0.0, 5.0);
+            buttons.push(button);
+        }
+        let mut presets = Vec::new();
+        anchor = Anchor::center_below(buttons.last().unwrap().rect(), 0.0, 20.0);
+        for preset in Preset::ALL {
+            let mut button = create_button(&format!("Use {}", preset.name()), anchor);
+            button.interact();
+            anchor = Anchor::center_below(button.rect(), 0.0, 5.0);
+            presets.push(button);
+        }
+        anchor = Anchor::center_below(presets.last().unwrap().rect(), 0.0, 20.0);
+        let mut back = create_button("Back", anchor);
+        back.interact();
+
+        let mut content = text.rect.combine_with(back.rect());
+        for button in buttons.iter().chain(&presets) {
+            content = content.combine_with(button.rect());
+        }
+        render_window(content, theme);
+        text.render_text(theme.ui_darker);
+        let all_buttons = buttons
+            .iter()
+            .chain(&presets)
+            .chain([&back])
+            .collect::<Vec<_>>();
+        let style = theme.style();
+        for button in &all_buttons {
+            button.render(&style);
+        }
+
+        let chosen = pad.choose("controls", &all_buttons, theme);
+        if let Some(command) = chosen.and_then(|i| Command::ALL.get(i)) {
+            self.waiting = Some(*command);
+        }
+        let chosen_preset = chosen.and_then(|i| i.checked_sub(buttons.len()));
+        if let Some(preset) = chosen_preset.and_then(|i| Preset::ALL.get(i)) {
+            let repeat = bindings.repeat;
+            *bindings = Bindings::preset(*preset);
+            bindings.repeat = repeat;
+        }
+        if chosen == Some(all_buttons.len() - 1) {
+            match bindings.save(DEFAULT_BINDINGS_PATH) {
+                Ok(()) => println!("key bindings saved to {}", DEFAULT_BINDINGS_PATH),
+                Err(e) => eprintln!(
+                    "could not save key bindings to {}: {}",
+                    DEFAULT_BINDINGS_PATH, e
+                ),
+            }
+            return true;
+        }
+        false
+    }
+}
+
+fn draw_game_won(last_level: bool, pad: &mut GamepadInput, theme: &Theme) -> bool {
+    let (message, button) = if last_level {
+        ("You won!", "Play again")
+    } else {
+        ("Level completed!", "Next level")
+    };
     let text_anchor = Anchor::top_center(screen_width() * 0.5, screen_height() * 0.45);
-    let text = TextRect::new("You won!", text_anchor, FONT_SIZE);
+    let text = TextRect::new(message, text_anchor, FONT_SIZE);
 
     let button_anchor = Anchor::center_below(text.rect, 0.0, 20.0);
-    let mut resume = create_button("Restart", button_anchor);
+    let mut resume = create_button(button, button_anchor);
     resume.interact();
 
-    render_window(text.rect.combine_with(resume.rect()));
-    text.render_text(COLOR_UI_DARKER);
-    resume.render(&STYLE);
-    resume.interaction()
+    render_window(text.rect.combine_with(resume.rect()), theme);
+    text.render_text(theme.ui_darker);
+    resume.render(&theme.style());
+    pad.choose("won", &[&resume], theme).is_some()
 }
-fn draw_respawn_ui() -> Interaction {
+fn draw_respawn_ui(pad: &mut GamepadInput, theme: &Theme) -> bool {
     let text_anchor = Anchor::top_center(screen_width() * 0.5, screen_height() * 0.475);
     let text = TextRect::new("You died", text_anchor, FONT_SIZE);
 
@@ -351,17 +1041,138 @@ fn draw_respawn_ui() -> Interaction {
     let mut retry = create_button("Retry", button_anchor);
     retry.interact();
 
-    render_window(text.rect.combine_with(retry.rect()));
-    text.render_text(COLOR_UI_DARKER);
-    retry.render(&STYLE);
-    retry.interaction()
+    render_window(text.rect.combine_with(retry.rect()), theme);
+    text.render_text(theme.ui_darker);
+    retry.render(&theme.style());
+    pad.choose("respawn", &[&retry], theme).is_some()
+}
+
+/// The gamepad next to the keyboard and mouse. It moves the player, and navigates the menus by
+/// focusing their buttons.
+struct GamepadInput {
+    gamepad: Gamepad,
+    source: GamepadSource,
+    focus: Focus,
+}
+
+impl GamepadInput {
+    fn open() -> Self {
+        Self {
+            gamepad: Gamepad::default(),
+            source: GamepadSource::open(),
+            focus: Focus::default(),
+        }
+    }
+
+    /// Call this once per frame.
+    fn poll(&mut self) {
+        self.gamepad.begin_frame();
+        for event in self.source.poll() {
+            self.gamepad.handle(event);
+        }
+    }
+
+    /// Call this after rendering the buttons of `menu`. Returns the index of the button clicked
+    /// with the mouse or confirmed with the gamepad.
+    fn choose(&mut self, menu: &'static str, buttons: &[&Button], theme: &Theme) -> Option<usize> {
+        let confirmed = self.focus.update(menu, buttons.len(), &self.gamepad);
+        if self.gamepad.used {
+            let focused = buttons[self.focus.index()].rect();
+            let pad = 3.0;
+            let outline = Rect::new(
+                focused.x - pad,
+                focused.y - pad,
+                focused.w + 2.0 * pad,
+                focused.h + 2.0 * pad,
+            );
+            draw_rect_lines(outline, 2.0, theme.ui_darker);
+        }
+        buttons
+            .iter()
+            .position(|button| button.interaction().is_clicked())
+            .or(confirmed)
+    }
+}
+
+/// On-screen arrows, an attack button and a wait button in the bottom right corner, for touch
+/// screens. They scale with the window.
+struct DPad {
+    buttons: Vec<(Action, Button)>,
+}
+
+impl DPad {
+    fn new() -> Self {
+        let font_size = (screen_width() * 0.04).max(FONT_SIZE);
+        let cell = font_size * 2.5;
+        let margin = screen_width() * 0.03;
+        let center_x = screen_width() - margin - cell * 2.5;
+        let top = screen_height() - margin - cell * 3.0;
+        let arrows = [
+            (Action::MoveUp, "^", 0.0, 0.0),
+            (Action::MoveLeft, "<", -1.0, 1.0),
+            (Action::MoveRight, ">", 1.0, 1.0),
+            (Action::MoveDown, "v", 0.0, 2.0),
+            (Action::Attack, "x", -2.0, 2.0),
+            (Action::Wait, "z", 2.0, 2.0),
+        ];
+        let buttons = arrows
+            .into_iter()
+            .map(|(action, text, column, row)| {
+                let anchor = Anchor::top_center(center_x + column * cell, top + row * cell);
+                let mut button = create_sized_button(text, anchor, font_size);
+                button.interact();
+                (action, button)
+            })
+            .collect();
+        Self { buttons }
+    }
+
+    fn held_move(&self) -> Option<Action> {
+        self.buttons
+            .iter()
+            .find(|(action, button)| {
+                action.direction().is_some()
+                    && matches!(
+                        button.interaction(),
+                        Interaction::Pressing | Interaction::Clicked
+                    )
+            })
+            .map(|(action, _)| *action)
+    }
+
+    /// The action of a clicked button that is not an arrow.
+    fn clicked_action(&self) -> Option<Action> {
+        self.buttons
+            .iter()
+            .find(|(action, button)| {
+                action.direction().is_none() && button.interaction().is_clicked()
+            })
+            .map(|(action, _)| *action)
+    }
+
+    fn contains(&self, position: Vec2) -> bool {
+        self.buttons
+            .iter()
+            .any(|(_, button)| button.rect().contains(position))
+    }
+
+    fn render(&self, theme: &Theme) {
+        let style = theme.style();
+        for (_, button) in &self.buttons {
+            button.render(&style);
+        }
+    }
 }
 
 fn create_button(text: &str, anchor: Anchor) -> Button {
+    create_sized_button(text, anchor, FONT_SIZE)
+}
+
+fn create_sized_button(text: &str, anchor: Anchor, font_size: f32) -> Button {
     Button::new_generic(
         text,
         anchor,
-        FONT_SIZE,
+        font_size,
         measure_text,
         draw_text,
         render_button,
@@ -392,54 +1203,6 @@ pub fn draw_panel_border(rect: Rect, interaction: Interaction, style: &Interacti
     draw_rect_lines(rect, 2.0, color)
 }
 
-fn pixel_to_tile(x: Pixels, y: Pixels, tile_size: Pixels2) -> Coord2 {
-    Coord2::new((x / tile_size.x) as Coord, (y / tile_size.y) as Coord)
-}
-fn tile_to_pixel(x: Coord, y: Coord, tile_size: Pixels2) -> Pixels2 {
-    Pixels2::new(x as Pixels * tile_size.x, y as Pixels * tile_size.y)
-}
-
-const fn color_from_hex(mut hex: u32) -> Color {
-    let a = (hex & 0xFF) as u8;
-    hex >>= 8;
-    let b = (hex & 0xFF) as u8;
-    hex >>= 8;
-    let g = (hex & 0xFF) as u8;
-    hex >>= 8;
-    let r = (hex & 0xFF) as u8;
-    color_from_rgba(r, g, b, a)
-}
-
-pub const fn color_from_rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
-    Color::new(
-        r as f32 / 255.,
-        g as f32 / 255.,
-        b as f32 / 255.,
-        a as f32 / 255.,
-    )
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_generate_door() {
-        let screen_tiles = Coord2::new(4, 10);
-        assert_eq!(to_pos(0, screen_tiles), CoordDiff2::new(98, 195));
-        assert_eq!(to_pos(1, screen_tiles), CoordDiff2::new(99, 195));
-        assert_eq!(
-            to_pos((screen_tiles.x * screen_tiles.y) as i32 - 1, screen_tiles),
-            CoordDiff2::new(101, 204)
-        )
-    }
-
-    fn to_pos(i: i32, screen_tiles: UVec2) -> CoordDiff2 {
-        scalar_to_around_accumulated_pos(
-            CoordDiff2::new(100, 200),
-            Coord2::new(2, 5),
-            screen_tiles,
-            i,
-        )
-    }
+fn window_size() -> Pixels2 {
+    Pixels2::new(screen_width(), screen_height())
 }
@@ -1,5 +1,10 @@
-use macroquad::prelude::{IVec2, UVec2};
-use macroquad::rand::rand;
+use crate::chunk::ChunkStore;
+use crate::monster::{Monster, MonsterKind};
+use crate::rng::Rng;
+use crate::AnyError;
+use glam::{IVec2, UVec2};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::VecDeque;
 use std::ops::IndexMut;
 
 pub type Coord = u32;
@@ -16,87 +21,362 @@ pub const RIGHT: CoordDiff2 = CoordDiff2::new(1, 0);
 pub enum Tile {
     Floor,
     Wall,
-    Monster,
-    // Coin,
-    // Exit,
+    /// Only where monsters are generated or remembered. On the map they become a `Monster`
+    /// standing on floor.
+    Monster(MonsterKind),
+    Coin,
+    /// Gives back one point of health.
+    Health,
+    Exit,
 }
+impl Tile {
+    pub fn to_char(self) -> char {
+        match self {
+            Tile::Floor => '.',
+            Tile::Wall => '#',
+            Tile::Monster(kind) => kind.to_char(),
+            Tile::Coin => 'c',
+            Tile::Health => 'h',
+            Tile::Exit => 'E',
+        }
+    }
+    pub fn from_char(c: char) -> Option<Tile> {
+        match c {
+            '.' => Some(Tile::Floor),
+            '#' => Some(Tile::Wall),
+            'c' => Some(Tile::Coin),
+            'h' => Some(Tile::Health),
+            'E' => Some(Tile::Exit),
+            _ => MonsterKind::from_char(c).map(Tile::Monster),
+        }
+    }
+}
+/// Percentage of generated tiles of each kind. The rest is floor.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TileDensity {
+    pub wall: u32,
+    pub coin: u32,
+    pub monster: u32,
+    #[serde(default)]
+    pub health: u32,
+}
+
+/// What an attack did to a monster.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Hit {
+    Killed,
+    /// The monster survived and was pushed one tile away from the player.
+    Pushed,
+    /// The monster survived but there was no room to push it.
+    Hurt,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum WorldMode {
+    /// Whatever leaves the view is forgotten, and new random terrain takes its place.
+    #[default]
+    Dream,
+    /// The terrain is a fixed function of the world coordinates, and whatever leaves the view is
+    /// remembered as it was, so walking back shows the same place.
+    Lucid,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Map {
+    /// Serialized as one string per column, using `Tile::to_char`.
+    #[serde(serialize_with = "serialize_columns")]
+    #[serde(deserialize_with = "deserialize_columns")]
     tiles: Vec<Vec<Tile>>,
+    monsters: Vec<Monster>,
     offset: Coord2,
     pub player: Coord2,
+    rng: Rng,
+    density: TileDensity,
+    /// World coordinates of the top left tile of the view. The player starts at (0, 0).
+    origin: CoordDiff2,
+    /// Only in lucid mode. The view is a window into this world.
+    world: Option<ChunkStore>,
 }
 
 impl Map {
-    pub fn new(screen_tiles: Coord2, player: Coord2) -> Self {
-        let mut tiles = Vec::new();
-        for _i_x in 0..screen_tiles.x {
-            let mut column = Vec::new();
-            for _i_y in 0..screen_tiles.y {
-                column.push(Self::generate_tile());
-            }
-            tiles.push(column);
-        }
+    pub fn new(
+        screen_tiles: Coord2,
+        player: Coord2,
+        seed: u64,
+        density: TileDensity,
+        mode: WorldMode,
+    ) -> Self {
+        let world = match mode {
+            WorldMode::Dream => None,
+            WorldMode::Lucid => Some(ChunkStore::new(seed, density)),
+        };
+        Self::with_world(screen_tiles, player, seed, density, world)
+    }
+
+    /// Like `new`, but in lucid mode the view shows an existing world, and the player starts at
+    /// its (0, 0).
+    pub fn with_world(
+        screen_tiles: Coord2,
+        player: Coord2,
+        seed: u64,
+        density: TileDensity,
+        world: Option<ChunkStore>,
+    ) -> Self {
+        let rng = Rng::new(seed);
+        let origin = -to_signed(player);
+        let tiles = vec![vec![Tile::Floor; screen_tiles.y as usize]; screen_tiles.x as usize];
         let offset = Coord2::new(0, 0);
         let mut map = Self {
             tiles,
+            monsters: Vec::new(),
             offset,
             player,
+            rng,
+            density,
+            origin,
+            world,
         };
         for i_x in 0..screen_tiles.x {
-            *map.get_mut(Coord2::new(i_x, player.y)) = Tile::Floor;
+            for i_y in 0..screen_tiles.y {
+                let pos = Coord2::new(i_x, i_y);
+                let world_pos = map.to_world(pos);
+                let tile = match &mut map.world {
+                    None => generate_tile(&mut map.rng, density),
+                    Some(world) => world.get(world_pos),
+                };
+                map.place(pos, world_pos, tile);
+            }
+        }
+        for i_x in 0..screen_tiles.x {
+            map.set(Coord2::new(i_x, player.y), Tile::Floor);
         }
         for i_y in 0..screen_tiles.y {
-            *map.get_mut(Coord2::new(player.x, i_y)) = Tile::Floor;
+            map.set(Coord2::new(player.x, i_y), Tile::Floor);
         }
+        let player_world = map.to_world(player);
+        map.monsters
+            .retain(|monster| monster.pos.x != player_world.x && monster.pos.y != player_world.y);
         map
     }
 
-    pub fn move_down(&mut self) -> bool {
-        self.move_to(DOWN)
-    }
-    pub fn move_up(&mut self) -> bool {
-        self.move_to(UP)
-    }
-    pub fn move_right(&mut self) -> bool {
-        self.move_to(RIGHT)
-    }
-    pub fn move_left(&mut self) -> bool {
-        self.move_to(LEFT)
-    }
-    pub fn move_to(&mut self, diff: CoordDiff2) -> bool {
-        if self.get_rel(self.player, diff) != Tile::Wall {
+    /// Returns the tile the player stepped onto, or None if a wall is in the way. Coins are
+    /// collected, so they are left as floor.
+    pub fn move_to(&mut self, diff: CoordDiff2) -> Option<Tile> {
+        let stepped_on = self.get_rel(self.player, diff);
+        if stepped_on != Tile::Wall {
             for i_y in 0..diff.y {
-                self.replace_row(i_y)
+                self.replace_row(i_y, diff)
             }
             for i_y in 0..-diff.y {
-                self.replace_row(self.size().y as CoordDiff - i_y - 1);
+                self.replace_row(self.size().y as CoordDiff - i_y - 1, diff);
             }
             for i_x in 0..diff.x {
-                self.replace_column(i_x)
+                self.replace_column(i_x, diff)
             }
             for i_x in 0..-diff.x {
-                self.replace_column(self.size().x as CoordDiff - i_x - 1);
+                self.replace_column(self.size().x as CoordDiff - i_x - 1, diff);
             }
             self.offset = self.add_coord(self.offset, diff);
-            true
+            self.origin += diff;
+            if matches!(stepped_on, Tile::Coin | Tile::Health) {
+                *self.get_mut(self.player) = Tile::Floor;
+            }
+            Some(stepped_on)
         } else {
-            false
+            None
+        }
+    }
+
+    /// Grows or shrinks the view to `screen_tiles`, with the player in the centre. The tiles that
+    /// are still in view are kept, and the new ones are generated, or taken from the lucid world.
+    pub fn resize(&mut self, screen_tiles: Coord2) {
+        let old_origin = self.origin;
+        let old_size = self.size();
+        let player = screen_tiles / 2;
+        let origin = self.to_world(self.player) - to_signed(player);
+        let in_view = |world_pos: CoordDiff2, origin: CoordDiff2, size: Coord2| {
+            let pos = world_pos - origin;
+            pos.cmpge(CoordDiff2::ZERO).all() && pos.cmplt(to_signed(size)).all()
+        };
+        let mut tiles = vec![vec![Tile::Floor; screen_tiles.y as usize]; screen_tiles.x as usize];
+        for i_x in 0..old_size.x {
+            for i_y in 0..old_size.y {
+                let pos = Coord2::new(i_x, i_y);
+                let world_pos = self.to_world(pos);
+                if in_view(world_pos, origin, screen_tiles) {
+                    let new_pos = world_pos - origin;
+                    tiles[new_pos.x as usize][new_pos.y as usize] = self.get(pos);
+                } else {
+                    let leaving_tile = self.remembered_tile(pos);
+                    if let Some(world) = &mut self.world {
+                        world.set(world_pos, leaving_tile);
+                    }
+                    self.monsters.retain(|monster| monster.pos != world_pos);
+                }
+            }
+        }
+        self.tiles = tiles;
+        self.offset = Coord2::new(0, 0);
+        self.origin = origin;
+        self.player = player;
+        for i_x in 0..screen_tiles.x {
+            for i_y in 0..screen_tiles.y {
+                let pos = Coord2::new(i_x, i_y);
+                let world_pos = self.to_world(pos);
+                if !in_view(world_pos, old_origin, old_size) {
+                    let tile = match &mut self.world {
+                        None => generate_tile(&mut self.rng, self.density),
+                        Some(world) => world.get(world_pos),
+                    };
+                    self.place(pos, world_pos, tile);
+                }
+            }
         }
     }
 
-    fn replace_row(&mut self, i_y: i32) {
+    fn replace_row(&mut self, i_y: i32, diff: CoordDiff2) {
         assert!(self.in_range_y(i_y));
         let i_y = i_y as Coord;
         for i_x in 0..self.size().x {
-            *self.get_mut(Coord2::new(i_x, i_y)) = Self::generate_tile();
+            self.replace_tile(Coord2::new(i_x, i_y), diff);
         }
     }
-    fn replace_column(&mut self, i_x: i32) {
+    fn replace_column(&mut self, i_x: i32, diff: CoordDiff2) {
         assert!(self.in_range_x(i_x));
         let i_x = i_x as Coord;
         for i_y in 0..self.size().y {
-            *self.get_mut(Coord2::new(i_x, i_y)) = Self::generate_tile();
+            self.replace_tile(Coord2::new(i_x, i_y), diff);
+        }
+    }
+    /// `pos` is about to leave the view because of a move in the `diff` direction, and its slot
+    /// in the ring buffer will show the tile that enters the view on the opposite side.
+    /// Monsters on the leaving tile despawn, although the lucid world remembers them.
+    fn replace_tile(&mut self, pos: Coord2, diff: CoordDiff2) {
+        let leaving = self.to_world(pos);
+        let entering = leaving + to_signed(self.size()) * diff.signum();
+        let leaving_tile = self.remembered_tile(pos);
+        self.monsters.retain(|monster| monster.pos != leaving);
+        let tile = match &mut self.world {
+            None => generate_tile(&mut self.rng, self.density),
+            Some(world) => {
+                world.set(leaving, leaving_tile);
+                world.get(entering)
+            }
+        };
+        self.place(pos, entering, tile);
+    }
+    /// Puts a generated or remembered tile in the slot `pos`, which shows `world_pos`. A monster
+    /// tile spawns a monster on floor, and walls crush any monster there.
+    fn place(&mut self, pos: Coord2, world_pos: CoordDiff2, tile: Tile) {
+        if matches!(tile, Tile::Wall | Tile::Monster(_)) {
+            self.monsters.retain(|monster| monster.pos != world_pos);
         }
+        *self.get_mut(pos) = match tile {
+            Tile::Monster(kind) => {
+                self.monsters.push(Monster::new(world_pos, kind));
+                Tile::Floor
+            }
+            tile => tile,
+        };
+    }
+    /// What the lucid world stores for `pos`. Monsters are only remembered when on floor, and
+    /// they come back with full health. The exit belongs to the level, which puts it back when
+    /// it's in view, so it's not stored.
+    fn remembered_tile(&self, pos: Coord2) -> Tile {
+        match (self.get(pos), self.monster_at(pos)) {
+            (Tile::Floor, Some(monster)) => Tile::Monster(monster.kind),
+            (Tile::Exit, _) => Tile::Floor,
+            (tile, _) => tile,
+        }
+    }
+    pub fn to_world(&self, pos: Coord2) -> CoordDiff2 {
+        self.origin + to_signed(pos)
+    }
+    /// Inverse of `to_world`, wrapping around the view like `add_coord`.
+    pub fn world_to_view(&self, world_pos: CoordDiff2) -> Coord2 {
+        let pos = world_pos - self.origin;
+        let size = to_signed(self.size());
+        Coord2::new(
+            pos.x.rem_euclid(size.x) as Coord,
+            pos.y.rem_euclid(size.y) as Coord,
+        )
+    }
+
+    /// Shortest path over the tiles that are not walls, as the direction of each step. Like
+    /// everything else on the map, it may cross the edges of the view to the opposite side.
+    /// Returns None if `to` can't be reached.
+    pub fn find_path(&self, from: Coord2, to: Coord2) -> Option<Vec<CoordDiff2>> {
+        let size = self.size();
+        let index = |pos: Coord2| (pos.x * size.y + pos.y) as usize;
+        let mut came_from: Vec<Option<CoordDiff2>> = vec![None; (size.x * size.y) as usize];
+        let mut pending = VecDeque::from([from]);
+        while let Some(pos) = pending.pop_front() {
+            if pos == to {
+                let mut path = Vec::new();
+                let mut pos = to;
+                while pos != from {
+                    let dir = came_from[index(pos)].unwrap();
+                    path.push(dir);
+                    pos = self.add_coord(pos, -dir);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for dir in [UP, DOWN, LEFT, RIGHT] {
+                let next = self.add_coord(pos, dir);
+                if next != from && came_from[index(next)].is_none() && self.get(next) != Tile::Wall
+                {
+                    came_from[index(next)] = Some(dir);
+                    pending.push_back(next);
+                }
+            }
+        }
+        None
+    }
+    /// Whether following `path` from `from` never steps on a wall.
+    pub fn is_path_clear(&self, from: Coord2, path: &[CoordDiff2]) -> bool {
+        let mut pos = from;
+        path.iter().all(|dir| {
+            pos = self.add_coord(pos, *dir);
+            self.get(pos) != Tile::Wall
+        })
+    }
+
+    /// The lucid world, including what is on screen.
+    pub fn world(&self) -> Option<ChunkStore> {
+        let mut world = self.world.clone()?;
+        for i_x in 0..self.size().x {
+            for i_y in 0..self.size().y {
+                let pos = Coord2::new(i_x, i_y);
+                world.set(self.to_world(pos), self.remembered_tile(pos));
+            }
+        }
+        Some(world)
+    }
+
+    pub fn save_world(&self, path: &str) -> Result<(), AnyError> {
+        let world = self
+            .world()
+            .ok_or("only the lucid mode has a world to save")?;
+        world.save(path)
+    }
+
+    /// Replaces the lucid world with a saved one, and shows it around the current position.
+    pub fn load_world(&mut self, path: &str) -> Result<(), AnyError> {
+        if self.world.is_none() {
+            return Err("only the lucid mode can load a world".into());
+        }
+        let mut world = ChunkStore::load(path)?;
+        self.monsters.clear();
+        for i_x in 0..self.size().x {
+            for i_y in 0..self.size().y {
+                let pos = Coord2::new(i_x, i_y);
+                let world_pos = self.to_world(pos);
+                self.place(pos, world_pos, world.get(world_pos));
+            }
+        }
+        self.world = Some(world);
+        Ok(())
     }
     pub fn get(&self, pos: Coord2) -> Tile {
         let Coord2 {
@@ -108,36 +388,96 @@ impl Map {
             (pos.y + self.offset.y) % size_y,
         )
     }
-    pub fn advance(&mut self) {
-        let mut staring_monsters = Vec::new();
-        for (i_x, column) in self.tiles.iter().enumerate() {
-            for (i_y, tile) in column.iter().enumerate() {
-                let monster = self.raw_to_coord(i_x as u32, i_y as u32);
-                if *tile == Tile::Monster {
-                    if (self.player.x == monster.x) != (self.player.y == monster.y) {
-                        staring_monsters.push(monster);
-                    }
-                }
+    /// Like placing a generated tile: `Tile::Monster` spawns a monster.
+    pub fn set(&mut self, pos: Coord2, tile: Tile) {
+        self.place(pos, self.to_world(pos), tile);
+    }
+    pub fn monsters(&self) -> &[Monster] {
+        &self.monsters
+    }
+    pub fn monster_at(&self, pos: Coord2) -> Option<&Monster> {
+        let world_pos = self.to_world(pos);
+        self.monsters
+            .iter()
+            .find(|monster| monster.pos == world_pos)
+    }
+    /// Hits the monsters on the player's tile and next to it. The ones that survive are pushed
+    /// away from the player and have to wait a full move period before moving again.
+    pub fn attack(&mut self) -> Vec<Hit> {
+        let mut hits = Vec::new();
+        for dir in [CoordDiff2::ZERO, UP, RIGHT, DOWN, LEFT] {
+            let pos = self.add_coord(self.player, dir);
+            let world_pos = self.to_world(pos);
+            let Some(i) = self.monsters.iter().position(|m| m.pos == world_pos) else {
+                continue;
+            };
+            self.monsters[i].hp = self.monsters[i].hp.saturating_sub(1);
+            if self.monsters[i].hp == 0 {
+                self.monsters.remove(i);
+                hits.push(Hit::Killed);
+                continue;
+            }
+            let behind = self.add_coord(pos, dir);
+            if dir != CoordDiff2::ZERO
+                && self.get(behind) != Tile::Wall
+                && self.monster_at(behind).is_none()
+            {
+                // like the wall check, wrapping around the view
+                let behind = self.to_world(behind);
+                let monster = &mut self.monsters[i];
+                monster.pos = behind;
+                monster.cooldown = monster.kind.move_period();
+                hits.push(Hit::Pushed);
+            } else {
+                hits.push(Hit::Hurt);
+            }
+        }
+        hits
+    }
+    /// Moves all the monsters at once. Every monster that is ready picks a target tile looking at
+    /// the map as it was before the tick, and then:
+    /// - a monster can't enter a wall or a tile that was occupied when the tick started, so
+    ///   monsters don't follow each other in a chain and don't swap places;
+    /// - if several monsters pick the same tile, none of them moves.
+    ///
+    /// A monster that stays doesn't start its cooldown, so it tries again on the next tick.
+    ///
+    /// Returns where each monster that moved came from and went to, in world coordinates.
+    pub fn advance(&mut self) -> Vec<(CoordDiff2, CoordDiff2)> {
+        let occupied = self
+            .monsters
+            .iter()
+            .map(|monster| monster.pos)
+            .collect::<Vec<_>>();
+        let mut targets = vec![None; self.monsters.len()];
+        for (i, target) in targets.iter_mut().enumerate() {
+            let monster = self.monsters[i];
+            if monster.cooldown > 0 {
+                continue;
             }
+            let pos = self.world_to_view(monster.pos);
+            let random = self.rng.rand();
+            *target = monster
+                .kind
+                .next_step(self, pos, random)
+                .map(|dir| self.add_coord(pos, dir))
+                .filter(|next| self.get(*next) != Tile::Wall)
+                .map(|next| self.to_world(next))
+                .filter(|next| !occupied.contains(next));
         }
-        for monster_old_pos in staring_monsters {
-            let (dir, visible) = self.can_view(monster_old_pos, self.player);
-            if visible {
-                let monster_new_pos = self.add_coord(monster_old_pos, dir);
-                let monster_new = self.get_mut(monster_new_pos);
-                if *monster_new == Tile::Floor {
-                    *monster_new = Tile::Monster;
-                    *self.get_mut(monster_old_pos) = Tile::Floor;
+        let mut moves = Vec::new();
+        for (monster, target) in self.monsters.iter_mut().zip(&targets) {
+            if monster.cooldown > 0 {
+                monster.cooldown -= 1;
+            } else if let Some(target) = *target {
+                if targets.iter().filter(|t| **t == Some(target)).count() == 1 {
+                    moves.push((monster.pos, target));
+                    monster.pos = target;
+                    monster.cooldown = monster.kind.move_period() - 1;
                 }
             }
         }
-    }
-
-    fn raw_to_coord(&self, i_x: u32, i_y: u32) -> Coord2 {
-        self.add_coord(
-            Coord2::new(i_x, i_y),
-            -CoordDiff2::new(self.offset.x as i32, self.offset.y as i32),
-        )
+        moves
     }
 }
 impl Map {
@@ -160,10 +500,10 @@ impl Map {
             (pos.y + self.offset.y) % size_y,
         )
     }
-    fn size(&self) -> Coord2 {
+    pub fn size(&self) -> Coord2 {
         size(&self.tiles)
     }
-    fn add_coord(&self, pos: Coord2, diff: CoordDiff2) -> Coord2 {
+    pub fn add_coord(&self, pos: Coord2, diff: CoordDiff2) -> Coord2 {
         let size = self.size();
         let unsigned_diff = Coord2::new(
             (diff.x + size.x as CoordDiff) as Coord,
@@ -178,7 +518,7 @@ impl Map {
         0 <= x && x < self.size().x as CoordDiff
     }
     /// returns the direction of pos->target and whether that path is unobstructed
-    fn can_view(&self, mut pos: Coord2, target: Coord2) -> (CoordDiff2, bool) {
+    pub fn can_view(&self, mut pos: Coord2, target: Coord2) -> (CoordDiff2, bool) {
         assert_ne!(pos, target);
         assert!(pos.x == target.x || pos.y == target.y);
         if pos.x == target.x {
@@ -189,7 +529,7 @@ impl Map {
                     return (dir, false);
                 }
             }
-            return (dir, true);
+            (dir, true)
         } else if pos.y == target.y {
             let dir = if target.x > pos.x { RIGHT } else { LEFT };
             while pos.x != target.x {
@@ -198,22 +538,419 @@ impl Map {
                     return (dir, false);
                 }
             }
-            return (dir, true);
+            (dir, true)
         } else {
             unreachable!()
         }
     }
-    fn generate_tile() -> Tile {
-        let random = rand() % 100;
-        if random < 49 {
-            Tile::Wall
-        } else if random < 98 {
-            Tile::Floor
-        } else {
-            Tile::Monster
-        }
+}
+fn generate_tile(rng: &mut Rng, density: TileDensity) -> Tile {
+    tile_from_random(rng.rand(), density)
+}
+/// The lowest digits of `random` pick the kind of tile, and the rest pick the kind of monster.
+pub fn tile_from_random(random: u32, density: TileDensity) -> Tile {
+    let percent = random % 100;
+    if percent < density.wall {
+        Tile::Wall
+    } else if percent < density.wall + density.coin {
+        Tile::Coin
+    } else if percent < density.wall + density.coin + density.monster {
+        let kinds = MonsterKind::ALL;
+        Tile::Monster(kinds[(random / 100) as usize % kinds.len()])
+    } else if percent < density.wall + density.coin + density.monster + density.health {
+        Tile::Health
+    } else {
+        Tile::Floor
     }
 }
-fn size(tiles: &Vec<Vec<Tile>>) -> Coord2 {
+fn serialize_columns<S: Serializer>(tiles: &[Vec<Tile>], serializer: S) -> Result<S::Ok, S::Error> {
+    let columns = tiles
+        .iter()
+        .map(|column| column.iter().map(|tile| tile.to_char()).collect::<String>())
+        .collect::<Vec<_>>();
+    columns.serialize(serializer)
+}
+fn deserialize_columns<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Vec<Tile>>, D::Error> {
+    let columns = Vec::<String>::deserialize(deserializer)?;
+    let to_tile = |c| {
+        Tile::from_char(c).ok_or_else(|| serde::de::Error::custom(format!("unknown tile '{}'", c)))
+    };
+    let tiles = columns
+        .iter()
+        .map(|column| column.chars().map(to_tile).collect::<Result<Vec<_>, _>>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let height = tiles.first().map_or(0, |column| column.len());
+    if height == 0 || tiles.iter().any(|column| column.len() != height) {
+        return Err(serde::de::Error::custom(
+            "the map should be a non-empty rectangle",
+        ));
+    }
+    Ok(tiles)
+}
+fn size(tiles: &[Vec<Tile>]) -> Coord2 {
     Coord2::new(tiles.len() as Coord, tiles[0].len() as Coord)
 }
+
+pub fn to_signed(pos: Coord2) -> CoordDiff2 {
+    CoordDiff2::new(pos.x as CoordDiff, pos.y as CoordDiff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_path, EMPTY_DENSITY};
+    use proptest::prelude::*;
+
+    const SIZE: Coord2 = Coord2::new(9, 7);
+    const PLAYER: Coord2 = Coord2::new(4, 3);
+    const DENSITY: TileDensity = TileDensity {
+        wall: 49,
+        coin: 2,
+        monster: 2,
+        health: 0,
+    };
+
+    #[test]
+    fn test_same_seed_same_map() {
+        let mut a = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Dream);
+        let mut b = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Dream);
+        assert_eq!(a, b);
+        for dir in [RIGHT, RIGHT, DOWN, LEFT, UP, UP] {
+            assert_eq!(a.move_to(dir), b.move_to(dir));
+            a.advance();
+            b.advance();
+            assert_eq!(a, b);
+        }
+        assert_ne!(
+            a.tiles,
+            Map::new(SIZE, PLAYER, 42001, DENSITY, WorldMode::Dream).tiles
+        );
+    }
+
+    #[test]
+    fn test_find_path() {
+        let mut map = Map::new(
+            Coord2::new(5, 4),
+            Coord2::new(0, 0),
+            1,
+            DENSITY,
+            WorldMode::Dream,
+        );
+        let rows = [".#.#.", "...#.", "####.", "....."];
+        for (i_y, row) in rows.iter().enumerate() {
+            for (i_x, c) in row.chars().enumerate() {
+                map.set(
+                    Coord2::new(i_x as Coord, i_y as Coord),
+                    Tile::from_char(c).unwrap(),
+                );
+            }
+        }
+        let from = Coord2::new(0, 0);
+        assert_eq!(map.find_path(from, from), Some(vec![]));
+        assert_eq!(
+            map.find_path(from, Coord2::new(1, 1)),
+            Some(vec![DOWN, RIGHT])
+        );
+        assert_eq!(map.find_path(from, Coord2::new(1, 0)), None);
+        assert_eq!(map.find_path(from, Coord2::new(4, 0)), Some(vec![LEFT]));
+
+        let to = Coord2::new(3, 3);
+        let path = map.find_path(from, to).unwrap();
+        assert_eq!(path.len(), 3);
+        assert!(map.is_path_clear(from, &path));
+        map.set(Coord2::new(0, 3), Tile::Wall);
+        map.set(Coord2::new(4, 3), Tile::Wall);
+        assert!(!map.is_path_clear(from, &path));
+        assert_eq!(map.find_path(from, to).map(|path| path.len()), Some(6));
+    }
+
+    #[test]
+    fn test_maps_do_not_share_randomness() {
+        let mut alone = Map::new(SIZE, PLAYER, 7, DENSITY, WorldMode::Dream);
+        alone.move_to(RIGHT);
+
+        let mut interleaved = Map::new(SIZE, PLAYER, 7, DENSITY, WorldMode::Dream);
+        let mut other = Map::new(SIZE, PLAYER, 8, DENSITY, WorldMode::Dream);
+        other.move_to(DOWN);
+        interleaved.move_to(RIGHT);
+        other.move_to(DOWN);
+        assert_eq!(alone, interleaved);
+    }
+
+    #[test]
+    fn test_density() {
+        let only_walls = TileDensity {
+            wall: 100,
+            coin: 0,
+            monster: 0,
+            health: 0,
+        };
+        let mut map = Map::new(SIZE, PLAYER, 42000, only_walls, WorldMode::Dream);
+        assert_eq!(map.get(Coord2::new(0, 0)), Tile::Wall);
+        assert_eq!(map.get(PLAYER), Tile::Floor);
+        assert_eq!(map.move_to(RIGHT), Some(Tile::Floor));
+        assert_eq!(map.move_to(DOWN), None);
+    }
+
+    #[test]
+    fn test_lucid_mode_remembers() {
+        let mut map = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Lucid);
+        let left_of_player = map.add_coord(PLAYER, LEFT);
+        map.set(left_of_player, Tile::Coin);
+        let start = map.clone();
+        let mut moved = Vec::new();
+        for dir in [RIGHT, RIGHT, RIGHT, RIGHT, DOWN, DOWN, RIGHT, DOWN, DOWN] {
+            if map.move_to(dir).is_some() {
+                moved.push(dir);
+            }
+        }
+        assert_ne!(get_all(&map), get_all(&start));
+        for dir in moved.iter().rev() {
+            assert!(map.move_to(-*dir).is_some());
+        }
+        assert_eq!(get_all(&map), get_all(&start));
+        assert_eq!(map.move_to(LEFT), Some(Tile::Coin));
+    }
+
+    #[test]
+    fn test_lucid_world_forgets_the_exit() {
+        let mut map = Map::new(SIZE, PLAYER, 42000, EMPTY_DENSITY, WorldMode::Lucid);
+        let left_of_player = map.add_coord(PLAYER, LEFT);
+        map.set(left_of_player, Tile::Exit);
+        assert_eq!(map.remembered_tile(left_of_player), Tile::Floor);
+    }
+
+    #[test]
+    fn test_lucid_world_persists_between_sessions() {
+        let mut map = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Lucid);
+        let far_away = Coord2::new(0, 0);
+        map.set(far_away, Tile::Coin);
+        let path = &temp_path("session_world.json");
+        map.save_world(path).unwrap();
+
+        let mut next_session = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Lucid);
+        next_session.load_world(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(get_all(&next_session), get_all(&map));
+        assert!(Map::new(SIZE, PLAYER, 1, DENSITY, WorldMode::Dream)
+            .save_world(path)
+            .is_err());
+    }
+
+    #[test]
+    fn test_lucid_terrain_depends_on_world_position() {
+        let mut a = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Lucid);
+        let mut b = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Lucid);
+        // different routes to the same place, along the cleared row and column
+        a.move_to(RIGHT);
+        a.move_to(RIGHT);
+        b.move_to(LEFT);
+        b.move_to(RIGHT);
+        b.move_to(RIGHT);
+        b.move_to(RIGHT);
+        // the column that entered from the right is only influenced by the world position
+        let right_column = SIZE.x - 1;
+        for i_y in 0..SIZE.y {
+            let pos = Coord2::new(right_column, i_y);
+            assert_eq!(a.get(pos), b.get(pos));
+        }
+    }
+
+    fn get_all(map: &Map) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for i_x in 0..SIZE.x {
+            for i_y in 0..SIZE.y {
+                tiles.push(map.get(Coord2::new(i_x, i_y)));
+            }
+        }
+        tiles
+    }
+
+    #[test]
+    fn test_new_map_clears_player_cross() {
+        let density = TileDensity {
+            wall: 0,
+            coin: 0,
+            monster: 100,
+            health: 0,
+        };
+        let map = Map::new(SIZE, PLAYER, 1, density, WorldMode::Dream);
+        let expected = (SIZE.x - 1) * (SIZE.y - 1);
+        assert_eq!(map.monsters().len(), expected as usize);
+        assert!(map.monster_at(Coord2::new(PLAYER.x, 0)).is_none());
+        assert!(map.monster_at(Coord2::new(0, PLAYER.y)).is_none());
+    }
+
+    #[test]
+    fn test_monsters_walk_over_tiles() {
+        let mut map = Map::new(SIZE, PLAYER, 1, EMPTY_DENSITY, WorldMode::Dream);
+        let coin = map.add_coord(PLAYER, RIGHT);
+        let monster = map.add_coord(coin, RIGHT);
+        map.set(coin, Tile::Coin);
+        map.set(monster, Tile::Monster(MonsterKind::Stalker));
+        assert_eq!(map.get(monster), Tile::Floor);
+
+        map.advance();
+        assert_eq!(map.get(coin), Tile::Coin);
+        assert_eq!(
+            map.monster_at(coin).map(|m| m.kind),
+            Some(MonsterKind::Stalker)
+        );
+        map.advance();
+        assert!(map.monster_at(PLAYER).is_some());
+        assert_eq!(map.get(coin), Tile::Coin);
+    }
+
+    #[test]
+    fn test_monsters_move_simultaneously() {
+        let mut map = Map::new(SIZE, PLAYER, 1, EMPTY_DENSITY, WorldMode::Dream);
+        let stalker = Tile::Monster(MonsterKind::Stalker);
+        map.set(Coord2::new(6, 3), stalker);
+        map.set(Coord2::new(7, 3), stalker);
+        map.advance();
+        assert!(map.monster_at(Coord2::new(5, 3)).is_some());
+        assert!(map.monster_at(Coord2::new(6, 3)).is_none());
+        assert!(map.monster_at(Coord2::new(7, 3)).is_some());
+
+        let mut map = Map::new(SIZE, PLAYER, 1, EMPTY_DENSITY, WorldMode::Dream);
+        map.set(Coord2::new(5, 3), Tile::Wall);
+        map.set(Coord2::new(4, 1), stalker);
+        map.set(Coord2::new(5, 2), Tile::Monster(MonsterKind::Chaser));
+        map.advance();
+        assert!(map.monster_at(Coord2::new(4, 2)).is_none());
+        assert!(map.monster_at(Coord2::new(4, 1)).is_some());
+        assert!(map.monster_at(Coord2::new(5, 2)).is_some());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_monsters_stay_on_floor(
+            seed: u64,
+            wall in 0..60u32,
+            monster in 1..40u32,
+            ticks in 1..30usize,
+        ) {
+            let density = TileDensity {
+                wall,
+                coin: 2,
+                monster,
+                health: 2,
+            };
+            let mut map = Map::new(SIZE, PLAYER, seed, density, WorldMode::Dream);
+            let count = map.monsters().len();
+            for _ in 0..ticks {
+                map.advance();
+                prop_assert_eq!(map.monsters().len(), count);
+                for (i, monster) in map.monsters().iter().enumerate() {
+                    prop_assert_ne!(map.get(map.world_to_view(monster.pos)), Tile::Wall);
+                    prop_assert!(map.monsters()[..i].iter().all(|other| other.pos != monster.pos));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_attack() {
+        let mut map = Map::new(SIZE, PLAYER, 1, EMPTY_DENSITY, WorldMode::Dream);
+        let right = map.add_coord(PLAYER, RIGHT);
+        let below = map.add_coord(PLAYER, DOWN);
+        map.set(right, Tile::Monster(MonsterKind::Chaser));
+        map.set(below, Tile::Monster(MonsterKind::Wanderer));
+        map.set(map.add_coord(right, RIGHT), Tile::Wall);
+        assert_eq!(map.attack(), vec![Hit::Hurt, Hit::Killed]);
+        assert_eq!(map.monsters().len(), 1);
+
+        map.set(map.add_coord(right, RIGHT), Tile::Floor);
+        map.set(
+            map.add_coord(PLAYER, UP),
+            Tile::Monster(MonsterKind::Chaser),
+        );
+        assert_eq!(map.attack(), vec![Hit::Pushed, Hit::Killed]);
+        let pushed = map.add_coord(map.add_coord(PLAYER, UP), UP);
+        assert_eq!(map.monster_at(pushed).map(|m| m.cooldown), Some(2));
+        assert_eq!(map.attack(), vec![]);
+
+        let player = Coord2::new(3, 1);
+        let mut map = Map::new(
+            Coord2::new(5, 3),
+            player,
+            1,
+            EMPTY_DENSITY,
+            WorldMode::Dream,
+        );
+        let edge = map.add_coord(player, RIGHT);
+        map.set(edge, Tile::Monster(MonsterKind::Chaser));
+        assert_eq!(map.attack(), vec![Hit::Pushed]);
+        assert!(map.monster_at(map.add_coord(edge, RIGHT)).is_some());
+    }
+
+    #[test]
+    fn test_resize() {
+        for mode in [WorldMode::Dream, WorldMode::Lucid] {
+            let mut map = Map::new(SIZE, PLAYER, 1, EMPTY_DENSITY, mode);
+            map.move_to(RIGHT);
+            map.move_to(DOWN);
+            let player_world = map.to_world(PLAYER);
+            map.set(map.add_coord(PLAYER, RIGHT), Tile::Coin);
+            map.set(Coord2::new(0, 0), Tile::Monster(MonsterKind::Wanderer));
+            let monster_world = map.to_world(Coord2::new(0, 0));
+
+            map.resize(Coord2::new(15, 11));
+            assert_eq!(map.size(), Coord2::new(15, 11));
+            assert_eq!(map.player, Coord2::new(7, 5));
+            assert_eq!(map.to_world(map.player), player_world);
+            assert_eq!(map.get(map.add_coord(map.player, RIGHT)), Tile::Coin);
+            assert_eq!(map.monsters()[0].pos, monster_world);
+
+            map.resize(Coord2::new(3, 3));
+            assert_eq!(map.get(map.add_coord(map.player, RIGHT)), Tile::Coin);
+            assert!(map.monsters().is_empty());
+            map.resize(SIZE);
+            assert_eq!(map.player, PLAYER);
+            let remembered = mode == WorldMode::Lucid;
+            assert_eq!(map.monster_at(Coord2::new(0, 0)).is_some(), remembered);
+        }
+    }
+
+    #[test]
+    fn test_monsters_out_of_view() {
+        for mode in [WorldMode::Dream, WorldMode::Lucid] {
+            let mut map = Map::new(SIZE, PLAYER, 1, EMPTY_DENSITY, mode);
+            map.set(Coord2::new(0, 0), Tile::Monster(MonsterKind::Wanderer));
+            map.move_to(RIGHT);
+            assert!(map.monsters().is_empty());
+            map.move_to(LEFT);
+            let remembered = mode == WorldMode::Lucid;
+            assert_eq!(map.monster_at(Coord2::new(0, 0)).is_some(), remembered);
+        }
+    }
+
+    #[test]
+    fn test_collect_coin() {
+        let mut map = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Dream);
+        *map.get_mut(map.add_coord(PLAYER, RIGHT)) = Tile::Coin;
+        assert_eq!(map.move_to(RIGHT), Some(Tile::Coin));
+        assert_eq!(map.get(PLAYER), Tile::Floor);
+    }
+
+    #[test]
+    fn test_coins_are_forgotten_when_out_of_view() {
+        let mut map = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Dream);
+        *map.get_mut(Coord2::new(0, 0)) = Tile::Coin;
+        *map.get_mut(Coord2::new(1, 0)) = Tile::Coin;
+        map.move_to(RIGHT);
+        assert_eq!(map.get(Coord2::new(0, 0)), Tile::Coin);
+        map.move_to(LEFT);
+        assert_eq!(map.get(Coord2::new(1, 0)), Tile::Coin);
+        // the coin that went out of view was replaced by whatever got generated
+        let mut regenerated = Map::new(SIZE, PLAYER, 42000, DENSITY, WorldMode::Dream);
+        regenerated.move_to(RIGHT);
+        regenerated.move_to(LEFT);
+        assert_eq!(
+            map.get(Coord2::new(0, 0)),
+            regenerated.get(Coord2::new(0, 0))
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::map::{Coord2, CoordDiff2, Map, Tile, DOWN, LEFT, RIGHT, UP};
+use serde::{Deserialize, Serialize};
+
+/// Chasers only notice the player if there is a path this short to them.
+pub const CHASE_RADIUS: usize = 6;
+
+const DIRECTIONS: [CoordDiff2; 4] = [UP, RIGHT, DOWN, LEFT];
+
+/// A monster walking on the map. Monsters don't replace the tile they stand on.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Monster {
+    /// World position, see `Map::to_world`.
+    pub pos: CoordDiff2,
+    pub kind: MonsterKind,
+    /// Monster ticks left until it can move again.
+    pub cooldown: u32,
+    pub hp: u32,
+}
+
+impl Monster {
+    pub fn new(pos: CoordDiff2, kind: MonsterKind) -> Self {
+        Self {
+            pos,
+            kind,
+            cooldown: 0,
+            hp: kind.max_hp(),
+        }
+    }
+}
+
+/// How a monster decides where to go on each monster tick.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MonsterKind {
+    /// Steps toward the player when it sees them along a row or column.
+    Stalker,
+    /// Steps in a random direction.
+    Wanderer,
+    /// Moves randomly, but only to tiles next to a wall.
+    WallHugger,
+    /// Follows the shortest path to the player when they are within `CHASE_RADIUS` steps.
+    Chaser,
+}
+
+impl MonsterKind {
+    pub const ALL: [MonsterKind; 4] = [
+        MonsterKind::Stalker,
+        MonsterKind::Wanderer,
+        MonsterKind::WallHugger,
+        MonsterKind::Chaser,
+    ];
+
+    pub fn to_char(self) -> char {
+        match self {
+            MonsterKind::Stalker => 'M',
+            MonsterKind::Wanderer => 'W',
+            MonsterKind::WallHugger => 'H',
+            MonsterKind::Chaser => 'C',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<MonsterKind> {
+        MonsterKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_char() == c)
+    }
+
+    /// Monster ticks between two moves. The smarter kinds are slower.
+    pub fn move_period(self) -> u32 {
+        match self {
+            MonsterKind::Stalker | MonsterKind::WallHugger => 1,
+            MonsterKind::Wanderer | MonsterKind::Chaser => 2,
+        }
+    }
+
+    pub fn max_hp(self) -> u32 {
+        match self {
+            MonsterKind::Stalker | MonsterKind::Wanderer => 1,
+            MonsterKind::WallHugger | MonsterKind::Chaser => 2,
+        }
+    }
+
+    /// The direction the monster at `pos` wants to step in, if any. `random` is only used by the
+    /// kinds that move randomly.
+    pub fn next_step(self, map: &Map, pos: Coord2, random: u32) -> Option<CoordDiff2> {
+        match self {
+            MonsterKind::Stalker => {
+                let player = map.player;
+                if (player.x == pos.x) != (player.y == pos.y) {
+                    let (dir, visible) = map.can_view(pos, player);
+                    visible.then_some(dir)
+                } else {
+                    None
+                }
+            }
+            MonsterKind::Wanderer => Some(DIRECTIONS[random as usize % DIRECTIONS.len()]),
+            MonsterKind::WallHugger => {
+                let candidates = DIRECTIONS
+                    .into_iter()
+                    .filter(|dir| {
+                        let next = map.add_coord(pos, *dir);
+                        map.get(next) == Tile::Floor
+                            && DIRECTIONS
+                                .iter()
+                                .any(|around| map.get(map.add_coord(next, *around)) == Tile::Wall)
+                    })
+                    .collect::<Vec<_>>();
+                (!candidates.is_empty()).then(|| candidates[random as usize % candidates.len()])
+            }
+            MonsterKind::Chaser => map
+                .find_path(pos, map.player)
+                .filter(|path| path.len() <= CHASE_RADIUS)
+                .and_then(|path| path.first().copied()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::WorldMode;
+    use crate::test_util::EMPTY_DENSITY;
+
+    fn empty_map() -> Map {
+        Map::new(
+            Coord2::new(15, 15),
+            Coord2::new(7, 7),
+            1,
+            EMPTY_DENSITY,
+            WorldMode::Dream,
+        )
+    }
+
+    #[test]
+    fn test_stalker_and_chaser() {
+        let mut map = empty_map();
+        let stalker = MonsterKind::Stalker;
+        assert_eq!(stalker.next_step(&map, Coord2::new(7, 3), 0), Some(DOWN));
+        assert_eq!(stalker.next_step(&map, Coord2::new(6, 6), 0), None);
+        map.set(Coord2::new(7, 5), Tile::Wall);
+        assert_eq!(stalker.next_step(&map, Coord2::new(7, 3), 0), None);
+
+        let chaser = MonsterKind::Chaser;
+        assert!(chaser.next_step(&map, Coord2::new(7, 3), 0).is_some());
+        assert!(chaser.next_step(&map, Coord2::new(5, 5), 0).is_some());
+        assert_eq!(chaser.next_step(&map, Coord2::new(1, 1), 0), None);
+    }
+
+    #[test]
+    fn test_wall_hugger_stays_next_to_walls() {
+        let mut map = empty_map();
+        for i_y in 0..15 {
+            map.set(Coord2::new(2, i_y), Tile::Wall);
+        }
+        let hugger = MonsterKind::WallHugger;
+        for random in 0..8 {
+            let dir = hugger.next_step(&map, Coord2::new(3, 4), random).unwrap();
+            assert!(dir == UP || dir == DOWN);
+        }
+        assert_eq!(hugger.next_step(&map, Coord2::new(10, 4), 0), None);
+    }
+
+    #[test]
+    fn test_chars() {
+        for kind in MonsterKind::ALL {
+            assert_eq!(MonsterKind::from_char(kind.to_char()), Some(kind));
+        }
+    }
+}
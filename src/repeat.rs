@@ -0,0 +1,106 @@
+use crate::game::Action;
+use serde::{Deserialize, Serialize};
+
+/// How a held movement key keeps moving the player, in seconds.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RepeatConfig {
+    /// Time between pressing the key and the first repeated move.
+    pub delay: f32,
+    /// Time between repeated moves after that.
+    pub interval: f32,
+}
+
+impl Default for RepeatConfig {
+    fn default() -> Self {
+        Self {
+            delay: 0.25,
+            interval: 0.1,
+        }
+    }
+}
+
+/// Turns a held action into a move on press, then one move per interval after the delay.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Repeater {
+    held: Option<Action>,
+    elapsed: f32,
+    next_repeat: f32,
+    stopped: bool,
+}
+
+impl Repeater {
+    /// `held` is the action of the key that is down this frame, if any.
+    pub fn update(&mut self, config: RepeatConfig, held: Option<Action>, delta: f32) -> Action {
+        if held != self.held {
+            *self = Self {
+                held,
+                elapsed: 0.0,
+                next_repeat: config.delay,
+                stopped: false,
+            };
+            return held.unwrap_or(Action::Idle);
+        }
+        let action = match held {
+            Some(action) if !self.stopped => action,
+            _ => return Action::Idle,
+        };
+        self.elapsed += delta;
+        if self.elapsed >= self.next_repeat {
+            self.next_repeat += config.interval.max(f32::EPSILON);
+            action
+        } else {
+            Action::Idle
+        }
+    }
+
+    /// No more repeats until the key is released, e.g. because the player walked into a wall.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // powers of two, so that the frame times add up exactly
+    const CONFIG: RepeatConfig = RepeatConfig {
+        delay: 0.25,
+        interval: 0.125,
+    };
+    const FRAME: f32 = 0.0625;
+
+    fn moves(repeater: &mut Repeater, held: Option<Action>, frames: usize) -> usize {
+        (0..frames)
+            .filter(|_| repeater.update(CONFIG, held, FRAME) != Action::Idle)
+            .count()
+    }
+
+    #[test]
+    fn test_repeat_after_delay() {
+        let mut repeater = Repeater::default();
+        let held = Some(Action::MoveUp);
+        assert_eq!(repeater.update(CONFIG, held, FRAME), Action::MoveUp);
+        assert_eq!(moves(&mut repeater, held, 3), 0);
+        assert_eq!(moves(&mut repeater, held, 1), 1);
+        assert_eq!(moves(&mut repeater, held, 10), 5);
+
+        assert_eq!(moves(&mut repeater, None, 10), 0);
+        assert_eq!(repeater.update(CONFIG, held, FRAME), Action::MoveUp);
+    }
+
+    #[test]
+    fn test_stop_until_released() {
+        let mut repeater = Repeater::default();
+        let held = Some(Action::MoveLeft);
+        repeater.update(CONFIG, held, FRAME);
+        repeater.stop();
+        assert_eq!(moves(&mut repeater, held, 20), 0);
+
+        assert_eq!(
+            repeater.update(CONFIG, Some(Action::MoveDown), FRAME),
+            Action::MoveDown
+        );
+        assert_eq!(moves(&mut repeater, Some(Action::MoveDown), 5), 1);
+    }
+}
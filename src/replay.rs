@@ -0,0 +1,320 @@
+use crate::game::{Action, GameEvent, GameState, Pacing};
+use crate::map::{Coord, Coord2, WorldMode};
+use crate::save;
+use crate::versioned::load_json;
+use crate::AnyError;
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever an old replay would play out differently: when the layout of the saved games
+/// changes, since a replay can start from one, or when the generation or the rules change.
+const REPLAY_VERSION: u32 = 2;
+
+/// A recorded session: the initial conditions plus every non-idle action, stamped with the index
+/// of the `GameState::step` call it was applied at. Every other step was an `Action::Idle`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    /// Set when the session continued a saved game, instead of starting from the seed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved_game: Option<Box<GameState>>,
+    pub seed: u64,
+    pub mode: WorldMode,
+    pub pacing: Pacing,
+    pub margin: Coord,
+    pub screen_tiles: Coord2,
+    pub player: Coord2,
+    pub steps: u64,
+    pub actions: Vec<TimedAction>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TimedAction {
+    pub step: u64,
+    pub action: Action,
+}
+
+impl Replay {
+    pub fn new(
+        screen_tiles: Coord2,
+        player: Coord2,
+        seed: u64,
+        mode: WorldMode,
+        pacing: Pacing,
+        margin: Coord,
+    ) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            saved_game: None,
+            seed,
+            mode,
+            pacing,
+            margin,
+            screen_tiles,
+            player,
+            steps: 0,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn from_saved_game(game_state: GameState) -> Self {
+        let mut replay = Self::new(
+            game_state.map.size(),
+            game_state.map.player,
+            game_state.seed,
+            game_state.mode,
+            game_state.pacing,
+            game_state.margin,
+        );
+        replay.saved_game = Some(Box::new(game_state));
+        replay
+    }
+
+    /// Call this once for every action given to `GameState::step`, including `Action::Idle`.
+    pub fn record(&mut self, action: Action) {
+        if action != Action::Idle {
+            self.actions.push(TimedAction {
+                step: self.steps,
+                action,
+            });
+        }
+        self.steps += 1;
+    }
+
+    pub fn start(&self) -> GameState {
+        match &self.saved_game {
+            Some(game_state) => game_state.as_ref().clone(),
+            None => {
+                let mut game_state = GameState::new_at_level(
+                    self.screen_tiles,
+                    self.player,
+                    self.seed,
+                    self.mode,
+                    0,
+                    self.margin,
+                );
+                game_state.pacing = self.pacing;
+                game_state
+            }
+        }
+    }
+
+    pub fn playback(&self) -> Playback {
+        Playback {
+            replay: self.clone(),
+            step: 0,
+            next: 0,
+        }
+    }
+
+    pub fn run_headless(&self) -> GameState {
+        let mut game_state = self.start();
+        let mut playback = self.playback();
+        while let Some(action) = playback.next_action() {
+            game_state.step(action);
+        }
+        game_state
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), AnyError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Replays are shared in bug reports, so they are checked like saved games.
+    pub fn load(path: &str) -> Result<Self, AnyError> {
+        let replay: Replay = load_json(path, "replay", REPLAY_VERSION)?;
+        save::check_player(replay.screen_tiles, replay.player)?;
+        if let Some(game_state) = &replay.saved_game {
+            save::check(game_state)?;
+        }
+        for timed in &replay.actions {
+            if let Action::Resize(screen_tiles) = timed.action {
+                save::check_player(screen_tiles, screen_tiles / 2)?;
+            }
+        }
+        Ok(replay)
+    }
+}
+
+/// A game that records every step, so that it can be saved as a replay at any moment.
+pub struct Session {
+    pub game_state: GameState,
+    pub replay: Replay,
+}
+
+impl Session {
+    pub fn new(
+        screen_tiles: Coord2,
+        player: Coord2,
+        seed: u64,
+        mode: WorldMode,
+        pacing: Pacing,
+        margin: Coord,
+    ) -> Self {
+        let replay = Replay::new(screen_tiles, player, seed, mode, pacing, margin);
+        Self {
+            game_state: replay.start(),
+            replay,
+        }
+    }
+
+    /// Starts where `replay` starts, recording a new replay.
+    pub fn from_replay(replay: &Replay) -> Self {
+        let mut recording = replay.clone();
+        recording.steps = 0;
+        recording.actions.clear();
+        Self {
+            game_state: replay.start(),
+            replay: recording,
+        }
+    }
+
+    /// Continues a loaded game. The replay starts from it.
+    pub fn from_saved_game(game_state: GameState) -> Self {
+        Self {
+            replay: Replay::from_saved_game(game_state.clone()),
+            game_state,
+        }
+    }
+
+    pub fn step(&mut self, action: Action) -> Vec<GameEvent> {
+        self.replay.record(action);
+        self.game_state.step(action)
+    }
+}
+
+/// Yields the recorded actions one step at a time, filling the gaps with `Action::Idle`.
+pub struct Playback {
+    replay: Replay,
+    step: u64,
+    next: usize,
+}
+
+impl Playback {
+    pub fn next_action(&mut self) -> Option<Action> {
+        if self.is_finished() {
+            return None;
+        }
+        let action = match self.replay.actions.get(self.next) {
+            Some(timed) if timed.step == self.step => {
+                self.next += 1;
+                timed.action
+            }
+            _ => Action::Idle,
+        };
+        self.step += 1;
+        Some(action)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.step >= self.replay.steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::CAMPAIGN;
+    use crate::test_util::temp_path;
+
+    #[test]
+    fn test_replay_reproduces_session() {
+        let screen_tiles = Coord2::new(9, 7);
+        let player = Coord2::new(4, 3);
+        let mut session = Session::new(
+            screen_tiles,
+            player,
+            5,
+            WorldMode::Lucid,
+            Pacing::RealTime,
+            2,
+        );
+        let script = [
+            Action::MoveRight,
+            Action::Idle,
+            Action::MoveDown,
+            Action::Pause,
+            Action::MoveLeft,
+            Action::Pause,
+            Action::MoveUp,
+            Action::Idle,
+        ];
+        for i in 0..200 {
+            session.step(script[i % script.len()]);
+        }
+        session.step(Action::Resize(Coord2::new(13, 5)));
+        for i in 0..100 {
+            session.step(script[i % script.len()]);
+        }
+        session.step(Action::Restart);
+        for _ in 0..70 {
+            session.step(Action::Idle);
+        }
+
+        let serialized = serde_json::to_string(&session.replay).unwrap();
+        let loaded: Replay = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(loaded, session.replay);
+        assert_eq!(loaded.run_headless(), session.game_state);
+    }
+
+    #[test]
+    fn test_load_rejects_broken_replays() {
+        let mut game_state =
+            GameState::new(Coord2::new(9, 7), Coord2::new(4, 3), 5, WorldMode::Dream);
+        let mut replay = Replay::new(
+            Coord2::new(0, 0),
+            Coord2::new(0, 0),
+            5,
+            WorldMode::Dream,
+            Pacing::RealTime,
+            0,
+        );
+        let path = &temp_path("broken_replay.json");
+        replay.save(path).unwrap();
+        let empty_screen = Replay::load(path);
+        game_state.level = CAMPAIGN.len() as u32;
+        replay = Replay::from_saved_game(game_state);
+        replay.save(path).unwrap();
+        let missing_level = Replay::load(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(empty_screen.is_err());
+        assert!(missing_level.is_err());
+    }
+
+    #[test]
+    fn test_replay_from_saved_game() {
+        let mut game_state =
+            GameState::new(Coord2::new(9, 7), Coord2::new(4, 3), 5, WorldMode::Dream);
+        for _ in 0..30 {
+            game_state.step(Action::MoveUp);
+        }
+        let mut session = Session::from_saved_game(game_state);
+        for _ in 0..30 {
+            session.step(Action::MoveLeft);
+        }
+        let serialized = serde_json::to_string(&session.replay).unwrap();
+        let loaded: Replay = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(loaded.run_headless(), session.game_state);
+    }
+
+    #[test]
+    fn test_session_from_replay_of_saved_game() {
+        let mut game_state =
+            GameState::new(Coord2::new(9, 7), Coord2::new(4, 3), 5, WorldMode::Dream);
+        for _ in 0..30 {
+            game_state.step(Action::MoveUp);
+        }
+        let mut session = Session::from_saved_game(game_state);
+        for _ in 0..30 {
+            session.step(Action::MoveLeft);
+        }
+
+        let mut replayed = Session::from_replay(&session.replay);
+        let mut playback = session.replay.playback();
+        while let Some(action) = playback.next_action() {
+            replayed.step(action);
+        }
+        assert_eq!(replayed.game_state, session.game_state);
+        assert_eq!(replayed.replay.run_headless(), session.game_state);
+    }
+}
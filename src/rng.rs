@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+/// SplitMix64 generator. Each `Map` and `GameState` owns one, so that a seed fully determines
+/// the world and two worlds never disturb each other's randomness.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn rand(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+}
+
+/// Randomness that only depends on a position, so that it doesn't matter in which order the
+/// positions are visited.
+pub fn hash_position(seed: u64, x: i32, y: i32) -> u32 {
+    let position = ((x as u32 as u64) << 32) | y as u32 as u64;
+    Rng::new(seed ^ position).rand()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = Rng::new(42000);
+        let mut b = Rng::new(42000);
+        for _ in 0..100 {
+            assert_eq!(a.rand(), b.rand());
+        }
+        assert_ne!(Rng::new(1).rand(), Rng::new(2).rand());
+    }
+}
@@ -0,0 +1,134 @@
+//! Saved games are JSON files with this layout:
+//!
+//! ```text
+//! {
+//!   "version": 5,
+//!   "game_state": {
+//!     "seed": 42000,                 // seed the current level was generated from
+//!     "rng": {"state": ...},         // where the random sequence of the game is at
+//!     "mode": "Dream" | "Lucid",
+//!     "player_health": 5.0,
+//!     "map": {
+//!       "tiles": ["#.hc", ...],      // one string per column: '.' floor, '#' wall, 'c' coin,
+//!                                    // 'h' health, 'E' exit, see `Tile::to_char`
+//!       "monsters": [{"pos": [x, y], "kind": "Stalker", "cooldown": 0, "hp": 1}, ...],
+//!       "offset": [x, y],            // the tiles are a ring buffer starting at this position
+//!       "player": [x, y],            // position of the player on the screen
+//!       "rng": {"state": ...},
+//!       "density": {"wall": 49, "coin": 2, "monster": 2, "health": 1},
+//!       "origin": [x, y],            // world position of the top left tile
+//!       "world": null | {...}        // lucid mode only, same layout as the --world files
+//!     },
+//!     "doors_parts_collected": 0,
+//!     "score": 0,
+//!     "accumulated_pos": [x, y],     // world position of the player
+//!     "next_door": [x, y],
+//!     "exit": null | [x, y],
+//!     "escaped": false,
+//!     "level": 0,                    // index into the campaign
+//!     "paused": false,
+//!     "tick": 0,                     // steps simulated since the level started
+//!     "pacing": "RealTime" | "TurnBased",
+//!     "margin": 4,                   // map tiles beyond each edge of the screen
+//!     "attack_cooldown": 0,          // ticks until the player can attack again
+//!     "persistent_world": false      // restarts and new levels keep the --world world
+//!   }
+//! }
+//! ```
+//!
+//! The version is bumped whenever this layout changes, and older files are rejected. Replays
+//! embed saved games, so `REPLAY_VERSION` is bumped too.
+
+use crate::game::GameState;
+use crate::level::CAMPAIGN;
+use crate::map::Coord2;
+use crate::versioned::load_json;
+use crate::AnyError;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_SAVE_PATH: &str = "dream_maze_save.json";
+const SAVE_VERSION: u32 = 5;
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    game_state: GameState,
+}
+
+pub fn save(game_state: &GameState, path: &str) -> Result<(), AnyError> {
+    let save_file = SaveFile {
+        version: SAVE_VERSION,
+        game_state: game_state.clone(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&save_file)?)?;
+    Ok(())
+}
+
+pub fn load(path: &str) -> Result<GameState, AnyError> {
+    let save_file: SaveFile = load_json(path, "save", SAVE_VERSION)?;
+    check(&save_file.game_state)?;
+    Ok(save_file.game_state)
+}
+
+/// Rejects the edited or corrupted game states that would make the game panic.
+pub fn check(game_state: &GameState) -> Result<(), AnyError> {
+    if game_state.level as usize >= CAMPAIGN.len() {
+        return Err(format!("there is no level {}", game_state.level + 1).into());
+    }
+    check_player(game_state.map.size(), game_state.map.player)
+}
+
+/// Also rejects an empty map, which has no room for the player.
+pub fn check_player(size: Coord2, player: Coord2) -> Result<(), AnyError> {
+    if player.x >= size.x || player.y >= size.y {
+        return Err(format!("the player {} is outside the map {}", player, size).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Action;
+    use crate::map::WorldMode;
+    use crate::test_util::temp_path;
+
+    #[test]
+    fn test_save_and_load() {
+        for mode in [WorldMode::Dream, WorldMode::Lucid] {
+            let mut game_state = GameState::new(Coord2::new(9, 7), Coord2::new(4, 3), 5, mode);
+            for action in [Action::MoveRight, Action::MoveDown, Action::Idle] {
+                game_state.step(action);
+            }
+            let path = &temp_path("save.json");
+            save(&game_state, path).unwrap();
+            let mut loaded = load(path).unwrap();
+            std::fs::remove_file(path).unwrap();
+            assert_eq!(loaded, game_state);
+
+            for _ in 0..100 {
+                assert_eq!(
+                    loaded.step(Action::MoveLeft),
+                    game_state.step(Action::MoveLeft)
+                );
+            }
+            assert_eq!(loaded, game_state);
+        }
+    }
+
+    #[test]
+    fn test_reject_other_versions() {
+        let path = &temp_path("save_version.json");
+        let game_state = GameState::new(Coord2::new(9, 7), Coord2::new(4, 3), 5, WorldMode::Dream);
+        save(&game_state, path).unwrap();
+        let contents = std::fs::read_to_string(path).unwrap();
+        std::fs::write(
+            path,
+            contents.replacen("\"version\": 5", "\"version\": 4", 1),
+        )
+        .unwrap();
+        let result = load(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,24 @@
+//! Fixtures shared by the tests of several modules.
+
+use crate::map::TileDensity;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Only floor, so that the tests place whatever they need.
+pub const EMPTY_DENSITY: TileDensity = TileDensity {
+    wall: 0,
+    coin: 0,
+    monster: 0,
+    health: 0,
+};
+
+/// A path in the temporary directory that no other test uses, not even one running at the same
+/// time in another process. `name` only makes leftover files easier to recognise.
+pub fn temp_path(name: &str) -> String {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    let unique = NEXT.fetch_add(1, Ordering::Relaxed);
+    let file = format!("dream_maze_test_{}_{}_{}", std::process::id(), unique, name);
+    std::env::temp_dir()
+        .join(file)
+        .to_string_lossy()
+        .into_owned()
+}
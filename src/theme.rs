@@ -0,0 +1,279 @@
+use crate::versioned::load_json;
+use crate::AnyError;
+use juquad::widgets::button::{InteractionStyle, Style};
+use macroquad::color::{Color, DARKGRAY};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_THEME_PATH: &str = "dream_maze_theme.json";
+const THEME_VERSION: u32 = 1;
+
+/// The colours of everything on the screen. In a file, colours are hex strings like "#3E93CC", or
+/// "#3E93CC80" with transparency.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Theme {
+    pub version: u32,
+    /// Shown in the pause menu.
+    pub name: String,
+    /// Around the map, when the window is not a whole number of tiles.
+    #[serde(with = "hex")]
+    pub outside: Color,
+    #[serde(with = "hex")]
+    pub background: Color,
+    #[serde(with = "hex")]
+    pub wall: Color,
+    #[serde(with = "hex")]
+    pub door: Color,
+    #[serde(with = "hex")]
+    pub player: Color,
+    #[serde(with = "hex")]
+    pub stalker: Color,
+    #[serde(with = "hex")]
+    pub wanderer: Color,
+    #[serde(with = "hex")]
+    pub wall_hugger: Color,
+    #[serde(with = "hex")]
+    pub chaser: Color,
+    #[serde(with = "hex")]
+    pub coin: Color,
+    #[serde(with = "hex")]
+    pub health: Color,
+    #[serde(with = "hex")]
+    pub attack: Color,
+    /// Menu windows, and the text of buttons at rest.
+    #[serde(with = "hex")]
+    pub ui_background: Color,
+    #[serde(with = "hex")]
+    pub ui_lighter: Color,
+    /// Buttons at rest.
+    #[serde(with = "hex")]
+    pub ui: Color,
+    /// Text and borders.
+    #[serde(with = "hex")]
+    pub ui_darker: Color,
+    /// The debug info.
+    #[serde(with = "hex")]
+    pub text: Color,
+}
+
+// https://supercolorpalette.com/?scp=G0-hsl-E4A84E-B2DF49-45D945-41D2A7-3E93CC-483BC4-9F3DB8-AB3F75
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            version: THEME_VERSION,
+            name: "default".to_string(),
+            outside: color_from_hex(0xC7C7C7FF),
+            background: color_from_hex(0x3E93CCFF),
+            wall: color_from_hex(0xE4A84EFF),
+            door: color_from_hex(0x7C351DFF),
+            player: color_from_hex(0x45D945FF),
+            stalker: color_from_hex(0x9F3DB8FF),
+            wanderer: color_from_hex(0x483BC4FF),
+            wall_hugger: color_from_hex(0xB2DF49FF),
+            chaser: color_from_hex(0xAB3F75FF),
+            coin: color_from_hex(0xF5D547FF),
+            health: color_from_hex(0xD94545FF),
+            attack: color_from_hex(0xF9E1FFFF),
+            ui_background: color_from_hex(0xF9E1FFFF),
+            ui_lighter: color_from_hex(0xCB9FD5FF),
+            ui: color_from_hex(0x9C4CAEFF),
+            ui_darker: color_from_hex(0x4F2759FF),
+            text: color_from_hex(0x000000FF),
+        }
+    }
+}
+
+impl Theme {
+    /// The palettes that the pause menu cycles through, starting with the default one.
+    pub fn built_in() -> Vec<Theme> {
+        vec![
+            Theme::default(),
+            // https://jfly.uni-koeln.de/color/, distinguishable with any kind of colour blindness
+            Theme {
+                version: THEME_VERSION,
+                name: "colour-blind safe".to_string(),
+                outside: color_from_hex(0x999999FF),
+                background: color_from_hex(0xF2F2F2FF),
+                wall: color_from_hex(0x0072B2FF),
+                door: color_from_hex(0x000000FF),
+                player: color_from_hex(0x009E73FF),
+                stalker: color_from_hex(0xD55E00FF),
+                wanderer: color_from_hex(0xCC79A7FF),
+                wall_hugger: color_from_hex(0x56B4E9FF),
+                chaser: color_from_hex(0x7F7F7FFF),
+                coin: color_from_hex(0xE69F00FF),
+                health: color_from_hex(0xF0E442FF),
+                attack: color_from_hex(0x5C3A00FF),
+                ui_background: color_from_hex(0xFFFFFFFF),
+                ui_lighter: color_from_hex(0x56B4E9FF),
+                ui: color_from_hex(0x0072B2FF),
+                ui_darker: color_from_hex(0x000000FF),
+                text: color_from_hex(0x000000FF),
+            },
+            Theme {
+                version: THEME_VERSION,
+                name: "high contrast".to_string(),
+                outside: color_from_hex(0x404040FF),
+                background: color_from_hex(0x000000FF),
+                wall: color_from_hex(0xFFFFFFFF),
+                door: color_from_hex(0x00A0FFFF),
+                player: color_from_hex(0x00FF00FF),
+                stalker: color_from_hex(0xFF0000FF),
+                wanderer: color_from_hex(0x00FFFFFF),
+                wall_hugger: color_from_hex(0xFF8000FF),
+                chaser: color_from_hex(0xFF00FFFF),
+                coin: color_from_hex(0xFFFF00FF),
+                health: color_from_hex(0xFF80C0FF),
+                attack: color_from_hex(0xA0A0FFFF),
+                ui_background: color_from_hex(0x000000FF),
+                ui_lighter: color_from_hex(0x0000C0FF),
+                ui: color_from_hex(0xFFFF00FF),
+                ui_darker: color_from_hex(0xFFFFFFFF),
+                text: color_from_hex(0xFFFFFFFF),
+            },
+        ]
+    }
+
+    pub fn load(path: &str) -> Result<Self, AnyError> {
+        load_json(path, "theme", THEME_VERSION)
+    }
+
+    /// How the buttons look.
+    pub fn style(&self) -> Style {
+        Style {
+            text_color: InteractionStyle {
+                at_rest: self.ui_background,
+                hovered: self.ui_darker,
+                pressed: self.ui_lighter,
+            },
+            bg_color: InteractionStyle {
+                at_rest: self.ui,
+                hovered: self.ui_lighter,
+                pressed: self.ui_darker,
+            },
+            border_color: InteractionStyle {
+                at_rest: self.ui,
+                hovered: self.ui_darker,
+                pressed: DARKGRAY,
+            },
+        }
+    }
+}
+
+/// Parses "#RRGGBB" or "#RRGGBBAA". The '#' is optional.
+pub fn parse_hex(text: &str) -> Result<Color, AnyError> {
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    let value = u32::from_str_radix(digits, 16).ok();
+    match (digits.len(), value) {
+        _ if !digits.chars().all(|c| c.is_ascii_hexdigit()) => {}
+        (6, Some(value)) => return Ok(color_from_hex(value << 8 | 0xFF)),
+        (8, Some(value)) => return Ok(color_from_hex(value)),
+        _ => {}
+    }
+    Err(format!("invalid colour '{}', expected #RRGGBB or #RRGGBBAA", text).into())
+}
+
+/// The inverse of `parse_hex`. The alpha is only written if the colour is transparent.
+pub fn to_hex(color: Color) -> String {
+    let [r, g, b, a]: [u8; 4] = color.into();
+    if a == u8::MAX {
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    } else {
+        format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
+}
+
+pub const fn color_from_hex(mut hex: u32) -> Color {
+    let a = (hex & 0xFF) as u8;
+    hex >>= 8;
+    let b = (hex & 0xFF) as u8;
+    hex >>= 8;
+    let g = (hex & 0xFF) as u8;
+    hex >>= 8;
+    let r = (hex & 0xFF) as u8;
+    color_from_rgba(r, g, b, a)
+}
+
+pub const fn color_from_rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+    Color::new(
+        r as f32 / 255.,
+        g as f32 / 255.,
+        b as f32 / 255.,
+        a as f32 / 255.,
+    )
+}
+
+/// Serializes colours as hex strings.
+mod hex {
+    use super::{parse_hex, to_hex};
+    use macroquad::color::Color;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(*color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        parse_hex(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    #[test]
+    fn test_hex_colours() {
+        assert_eq!(parse_hex("#3E93CC").unwrap(), color_from_hex(0x3E93CCFF));
+        assert_eq!(parse_hex("3e93cc80").unwrap(), color_from_hex(0x3E93CC80));
+        assert_eq!(to_hex(color_from_hex(0x3E93CCFF)), "#3E93CC");
+        assert_eq!(to_hex(color_from_hex(0x3E93CC80)), "#3E93CC80");
+        for invalid in ["", "#3E93C", "#3E93CCF", "+3E93CC", "#3E93CG"] {
+            assert!(parse_hex(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_every_element_has_its_own_colour() {
+        for theme in Theme::built_in() {
+            let elements = [
+                theme.background,
+                theme.wall,
+                theme.door,
+                theme.player,
+                theme.stalker,
+                theme.wanderer,
+                theme.wall_hugger,
+                theme.chaser,
+                theme.coin,
+                theme.health,
+                theme.attack,
+            ];
+            for (i, color) in elements.iter().enumerate() {
+                let repeated = elements[i + 1..].contains(color);
+                assert!(!repeated, "{} repeats {}", theme.name, to_hex(*color));
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let themes = Theme::built_in();
+        let names = themes.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["default", "colour-blind safe", "high contrast"]);
+
+        let path = &temp_path("theme.json");
+        std::fs::write(path, serde_json::to_string_pretty(&themes[2]).unwrap()).unwrap();
+        let loaded = Theme::load(path);
+        let json = serde_json::to_string(&themes[1]).unwrap();
+        std::fs::write(path, json.replace("#009E73", "green")).unwrap();
+        let invalid_colour = Theme::load(path);
+        std::fs::write(path, json.replace("\"version\":1", "\"version\":0")).unwrap();
+        let old_version = Theme::load(path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.unwrap(), themes[2]);
+        assert!(invalid_colour.is_err());
+        assert!(old_version.is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::map::{Coord, Coord2, Map, Tile, DOWN, LEFT, RIGHT, UP};
+use crate::monster::MonsterKind;
+use crate::versioned::load_json;
+use crate::AnyError;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const DEFAULT_TILESET_PATH: &str = "dream_maze_tileset.json";
+const TILESET_VERSION: u32 = 1;
+/// One wall sprite for each combination of walls above, to the right, below and to the left.
+pub const WALL_VARIANTS: usize = 16;
+
+/// Everything that can be drawn with a sprite, apart from the walls.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Sprite {
+    Floor,
+    Coin,
+    Health,
+    Exit,
+    Door,
+    Player,
+    Stalker,
+    Wanderer,
+    WallHugger,
+    Chaser,
+}
+
+impl Sprite {
+    /// The sprite drawn on top of the floor for `tile`, if any. Monsters are drawn separately.
+    pub fn of_tile(tile: Tile) -> Option<Sprite> {
+        match tile {
+            Tile::Coin => Some(Sprite::Coin),
+            Tile::Health => Some(Sprite::Health),
+            Tile::Exit => Some(Sprite::Exit),
+            Tile::Floor | Tile::Wall | Tile::Monster(_) => None,
+        }
+    }
+
+    pub fn of_monster(kind: MonsterKind) -> Sprite {
+        match kind {
+            MonsterKind::Stalker => Sprite::Stalker,
+            MonsterKind::Wanderer => Sprite::Wanderer,
+            MonsterKind::WallHugger => Sprite::WallHugger,
+            MonsterKind::Chaser => Sprite::Chaser,
+        }
+    }
+}
+
+/// Where each sprite is in the atlas image, as the column and row of a cell of `tile_size` pixels.
+/// Sprites that are missing are drawn with the plain shapes used when there is no tileset.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TilesetMapping {
+    pub version: u32,
+    /// Path of the PNG atlas, relative to the mapping file.
+    pub image: String,
+    pub tile_size: Coord,
+    #[serde(default)]
+    pub sprites: BTreeMap<Sprite, [Coord; 2]>,
+    /// Indexed by `wall_neighbours`.
+    pub walls: Option<[[Coord; 2]; WALL_VARIANTS]>,
+}
+
+impl TilesetMapping {
+    pub fn load(path: &str) -> Result<Self, AnyError> {
+        let mapping: TilesetMapping = load_json(path, "tileset", TILESET_VERSION)?;
+        if mapping.tile_size == 0 {
+            return Err("the tileset tile_size should be positive".into());
+        }
+        Ok(mapping)
+    }
+
+    /// Path of the atlas, for a mapping loaded from `mapping_path`.
+    pub fn image_path(&self, mapping_path: &str) -> String {
+        let dir = Path::new(mapping_path).parent().unwrap_or(Path::new(""));
+        dir.join(&self.image).to_string_lossy().into_owned()
+    }
+
+    /// Top left corner of the sprite in the atlas, in pixels.
+    pub fn source(&self, sprite: Sprite) -> Option<Vec2> {
+        self.sprites
+            .get(&sprite)
+            .map(|cell| self.cell_to_pixel(*cell))
+    }
+
+    /// Top left corner in the atlas of the sprite for a wall with the given neighbours.
+    pub fn wall_source(&self, neighbours: usize) -> Option<Vec2> {
+        self.walls
+            .map(|walls| self.cell_to_pixel(walls[neighbours % WALL_VARIANTS]))
+    }
+
+    fn cell_to_pixel(&self, [column, row]: [Coord; 2]) -> Vec2 {
+        Vec2::new(column as f32, row as f32) * self.tile_size as f32
+    }
+}
+
+/// Which of the tiles next to `pos` are walls, as a bit mask: 1 above, 2 to the right, 4 below
+/// and 8 to the left. Used to pick the wall sprite that connects with its neighbours.
+pub fn wall_neighbours(map: &Map, pos: Coord2) -> usize {
+    [UP, RIGHT, DOWN, LEFT]
+        .into_iter()
+        .enumerate()
+        .filter(|(_, dir)| map.get(map.add_coord(pos, *dir)) == Tile::Wall)
+        .map(|(bit, _)| 1 << bit)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::WorldMode;
+    use crate::test_util::{temp_path, EMPTY_DENSITY};
+
+    #[test]
+    fn test_wall_neighbours() {
+        let mut map = Map::new(
+            Coord2::new(7, 7),
+            Coord2::new(0, 0),
+            1,
+            EMPTY_DENSITY,
+            WorldMode::Dream,
+        );
+        let center = Coord2::new(3, 3);
+        assert_eq!(wall_neighbours(&map, center), 0);
+        map.set(Coord2::new(3, 2), Tile::Wall);
+        map.set(Coord2::new(4, 3), Tile::Wall);
+        assert_eq!(wall_neighbours(&map, center), 1 | 2);
+        map.set(Coord2::new(2, 3), Tile::Wall);
+        map.set(Coord2::new(4, 4), Tile::Wall);
+        assert_eq!(wall_neighbours(&map, center), 1 | 2 | 8);
+    }
+
+    #[test]
+    fn test_load_mapping() {
+        let path = &temp_path("tileset.json");
+        std::fs::write(
+            path,
+            r#"{"version": 1, "image": "atlas.png", "tile_size": 16,
+                "sprites": {"Player": [2, 1]}}"#,
+        )
+        .unwrap();
+        let mapping = TilesetMapping::load(path);
+        std::fs::write(
+            path,
+            r#"{"version": 1, "image": "atlas.png", "tile_size": 0}"#,
+        )
+        .unwrap();
+        let zero_size = TilesetMapping::load(path);
+        std::fs::remove_file(path).unwrap();
+
+        let mapping = mapping.unwrap();
+        assert_eq!(mapping.source(Sprite::Player), Some(Vec2::new(32.0, 16.0)));
+        assert_eq!(mapping.source(Sprite::Coin), None);
+        assert_eq!(mapping.wall_source(3), None);
+        assert_eq!(
+            mapping.image_path("assets/tileset.json"),
+            Path::new("assets").join("atlas.png").to_string_lossy()
+        );
+        assert!(zero_size.is_err());
+    }
+}
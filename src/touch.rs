@@ -0,0 +1,67 @@
+use crate::game::Action;
+use glam::Vec2;
+
+/// Turns touches that travel far enough into moves, in the dominant direction of the gesture.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Swipes {
+    started: Vec<(u64, Vec2)>,
+}
+
+impl Swipes {
+    pub fn start(&mut self, id: u64, position: Vec2) {
+        self.started.retain(|(started_id, _)| *started_id != id);
+        self.started.push((id, position));
+    }
+
+    /// Returns the move if the touch `id` ended at least `min_distance` away from its start.
+    pub fn end(&mut self, id: u64, position: Vec2, min_distance: f32) -> Option<Action> {
+        let index = self
+            .started
+            .iter()
+            .position(|(started_id, _)| *started_id == id)?;
+        let (_, start) = self.started.remove(index);
+        let diff = position - start;
+        if diff.length() < min_distance {
+            None
+        } else if diff.x.abs() > diff.y.abs() {
+            Some(if diff.x > 0.0 {
+                Action::MoveRight
+            } else {
+                Action::MoveLeft
+            })
+        } else {
+            Some(if diff.y > 0.0 {
+                Action::MoveDown
+            } else {
+                Action::MoveUp
+            })
+        }
+    }
+
+    pub fn cancel(&mut self, id: u64) {
+        self.started.retain(|(started_id, _)| *started_id != id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swipes() {
+        let mut swipes = Swipes::default();
+        swipes.start(1, Vec2::new(100.0, 100.0));
+        swipes.start(2, Vec2::new(300.0, 300.0));
+        let up = swipes.end(2, Vec2::new(310.0, 200.0), 50.0);
+        let left = swipes.end(1, Vec2::new(20.0, 130.0), 50.0);
+        assert_eq!(up, Some(Action::MoveUp));
+        assert_eq!(left, Some(Action::MoveLeft));
+        assert_eq!(swipes.end(1, Vec2::new(20.0, 130.0), 50.0), None);
+
+        swipes.start(3, Vec2::new(100.0, 100.0));
+        assert_eq!(swipes.end(3, Vec2::new(120.0, 110.0), 50.0), None);
+        swipes.start(4, Vec2::new(100.0, 100.0));
+        swipes.cancel(4);
+        assert_eq!(swipes.end(4, Vec2::new(300.0, 100.0), 50.0), None);
+    }
+}
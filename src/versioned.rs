@@ -0,0 +1,55 @@
+use crate::AnyError;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+/// Just the version of a file, read before the rest so that an old file gets a clear error
+/// instead of whatever field failed to parse.
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+/// Reads a JSON file with a top level "version" field, which must be `expected`. `what` names
+/// the kind of file in the errors, e.g. "replay".
+pub fn load_json<T: DeserializeOwned>(
+    path: &str,
+    what: &str,
+    expected: u32,
+) -> Result<T, AnyError> {
+    let contents = std::fs::read_to_string(path)?;
+    let Version { version } = serde_json::from_str(&contents)?;
+    check_version(what, version, expected)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+pub fn check_version(what: &str, version: u32, expected: u32) -> Result<(), String> {
+    if version == expected {
+        Ok(())
+    } else {
+        Err(format!(
+            "unsupported {} version {} (expected {})",
+            what, version, expected
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_version_is_checked_first() {
+        let path = &temp_path("versioned.json");
+        std::fs::write(path, r#"{"version": 2, "other": 5}"#).unwrap();
+        let current = load_json::<BTreeMap<String, u32>>(path, "test", 2);
+        let old = load_json::<BTreeMap<String, String>>(path, "test", 3);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(current.unwrap()["other"], 5);
+        assert_eq!(
+            old.unwrap_err().to_string(),
+            "unsupported test version 2 (expected 3)"
+        );
+    }
+}
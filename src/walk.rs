@@ -0,0 +1,109 @@
+use crate::game::Action;
+use crate::map::{Coord2, CoordDiff2, Map};
+use std::collections::VecDeque;
+
+/// Walks the player to a clicked tile, one step per interval.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Walk {
+    /// World position, because the view scrolls with every step.
+    target: CoordDiff2,
+    path: VecDeque<CoordDiff2>,
+    /// Time left until the next step.
+    wait: f32,
+}
+
+impl Walk {
+    /// Returns None if the target can't be reached.
+    pub fn new(map: &Map, target: Coord2) -> Option<Self> {
+        let path = map.find_path(map.player, target)?;
+        Some(Self {
+            target: map.to_world(target),
+            path: path.into(),
+            wait: 0.0,
+        })
+    }
+
+    /// The first step is immediate. If the view regenerated a wall in the way, the path is
+    /// computed again, and the walk ends if the target is no longer reachable.
+    pub fn update(&mut self, map: &Map, interval: f32, delta: f32) -> Action {
+        self.wait -= delta;
+        if self.wait > 0.0 {
+            return Action::Idle;
+        }
+        self.wait = interval;
+        if !map.is_path_clear(map.player, self.path.make_contiguous()) {
+            let target = map.world_to_view(self.target);
+            self.path = map.find_path(map.player, target).unwrap_or_default().into();
+        }
+        self.path
+            .pop_front()
+            .and_then(Action::from_direction)
+            .unwrap_or(Action::Idle)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.path.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Tile, WorldMode};
+    use crate::test_util::EMPTY_DENSITY;
+
+    const SIZE: Coord2 = Coord2::new(9, 7);
+    const PLAYER: Coord2 = Coord2::new(4, 3);
+
+    fn walk_to_end(map: &mut Map, walk: &mut Walk) -> usize {
+        let mut steps = 0;
+        while !walk.is_finished() {
+            let dir = walk.update(map, 0.0, 1.0).direction().unwrap();
+            map.move_to(dir).unwrap();
+            steps += 1;
+        }
+        steps
+    }
+
+    #[test]
+    fn test_walk_to_target() {
+        let mut map = Map::new(SIZE, PLAYER, 5, EMPTY_DENSITY, WorldMode::Lucid);
+        let target = Coord2::new(6, 1);
+        let target_world = map.to_world(target);
+        let mut walk = Walk::new(&map, target).unwrap();
+        assert_eq!(walk_to_end(&mut map, &mut walk), 4);
+        assert_eq!(map.to_world(map.player), target_world);
+    }
+
+    #[test]
+    fn test_wait_between_steps() {
+        let map = Map::new(SIZE, PLAYER, 5, EMPTY_DENSITY, WorldMode::Dream);
+        let mut walk = Walk::new(&map, Coord2::new(8, 3)).unwrap();
+        assert_eq!(walk.update(&map, 0.5, 0.25), Action::MoveRight);
+        assert_eq!(walk.update(&map, 0.5, 0.25), Action::Idle);
+        assert_eq!(walk.update(&map, 0.5, 0.25), Action::MoveRight);
+    }
+
+    #[test]
+    fn test_recompute_when_blocked() {
+        let mut map = Map::new(SIZE, PLAYER, 5, EMPTY_DENSITY, WorldMode::Lucid);
+        let target = Coord2::new(7, 3);
+        let target_world = map.to_world(target);
+        let mut walk = Walk::new(&map, target).unwrap();
+        map.set(Coord2::new(5, 3), Tile::Wall);
+        assert_eq!(walk_to_end(&mut map, &mut walk), 5);
+        assert_eq!(map.to_world(map.player), target_world);
+
+        let mut walk = Walk::new(&map, Coord2::new(4, 1)).unwrap();
+        for pos in [
+            Coord2::new(4, 2),
+            Coord2::new(3, 1),
+            Coord2::new(5, 1),
+            Coord2::new(4, 0),
+        ] {
+            map.set(pos, Tile::Wall);
+        }
+        assert_eq!(walk.update(&map, 0.0, 1.0), Action::Idle);
+        assert!(walk.is_finished());
+    }
+}